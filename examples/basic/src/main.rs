@@ -1,17 +1,6 @@
-use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
-use std::thread;
-
-use bmd_speededitor::{self, Key};
-
-pub enum SpeedEditorEvent {
-    KeyEvent(Key, bool),
-    JogEvent(u8, i32),
-}
+use bmd_speededitor::{self, Event};
 
 fn main() {
-    let (key_tx, rx): (SyncSender<SpeedEditorEvent>, Receiver<SpeedEditorEvent>) = sync_channel(0);
-    let jog_tx = key_tx.clone();
-
     let mut se = bmd_speededitor::new().unwrap();
     se.on_connected(|| {
         println!("Connected to the device");
@@ -25,35 +14,26 @@ fn main() {
         println!("current keys are: {:?}", keys);
         Ok(())
     });
-    se.on_key(move |key, down| {
-        key_tx.send(SpeedEditorEvent::KeyEvent(key, down)).unwrap();
-        Ok(())
-    });
-    se.on_jog(move |mode, value| {
-        jog_tx
-            .send(SpeedEditorEvent::JogEvent(mode, value))
-            .unwrap();
-        Ok(())
-    });
     se.on_unknown(|data| {
         println!("unknown event: {:?}", data);
         Ok(())
     });
 
-    let handle = thread::spawn(move || {
-        se.run().unwrap();
-    });
-
-    for e in rx {
-        match e {
-            SpeedEditorEvent::KeyEvent(key, down) => {
-                println!("key event: {} {}", key, down);
+    for e in se.events() {
+        match e.unwrap() {
+            Event::KeyDown(key) => {
+                println!("key event: {} true", key);
             }
-            SpeedEditorEvent::JogEvent(mode, value) => {
+            Event::KeyUp(key) => {
+                println!("key event: {} false", key);
+            }
+            Event::Jog(mode, value) => {
                 println!("jog: {} {}", mode, value);
             }
+            Event::Battery(status) => {
+                println!("battery: {}", status);
+            }
+            _ => {}
         }
     }
-
-    handle.join().unwrap();
 }
@@ -0,0 +1,17 @@
+use bmd_speededitor::{self, SpeedEditorError, WsServer};
+use std::env;
+use std::net::SocketAddr;
+
+// Usage: websocket [listen on, default 127.0.0.1:8765]
+fn main() -> Result<(), SpeedEditorError> {
+    let addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:8765".to_string());
+    let addr: SocketAddr = addr.parse().map_err(|e| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{}: {}", addr, e))
+    })?;
+
+    let server = WsServer::bind(addr)?;
+    println!("serving ws://{}", server.local_addr()?);
+    server.run(bmd_speededitor::new()?)
+}
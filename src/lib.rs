@@ -1,25 +1,113 @@
 mod speed_editor;
 
+use std::collections::VecDeque;
+
+#[cfg(feature = "tokio")]
+pub use speed_editor::async_speed_editor::AsyncSpeedEditor;
+#[cfg(feature = "midir")]
+pub use speed_editor::midi::MidirBackend;
+#[cfg(feature = "midi")]
+pub use speed_editor::midi::{
+    ByteSink, MidiBackend, MidiBridge, MidiConfig, MidiMapping, RelativeEncoding,
+};
+#[cfg(feature = "rumqttc")]
+pub use speed_editor::mqtt::RumqttcClient;
+#[cfg(feature = "mqtt")]
+pub use speed_editor::mqtt::{topic_matches, LocalBroker, MqttBridge, MqttClient, MqttConfig};
+#[cfg(feature = "osc")]
+pub use speed_editor::osc::{OscArg, OscBridge, OscConfig};
+#[cfg(feature = "profile")]
+pub use speed_editor::profile::{
+    Action, Actions, Binding, JogProfile, KeyBinding, Keymap, LayerProfile, LedRule, Profile,
+    Trigger,
+};
+#[cfg(feature = "hidapi")]
+pub use speed_editor::transport::{list_devices, HidConnector};
+#[cfg(feature = "websocket")]
+pub use speed_editor::websocket::{event_json, state_json, WsServer};
 pub use speed_editor::{
+    auth,
+    battery::BatteryStatus,
+    capture::{read_capture, CaptureConnector, CaptureKind, CaptureRecord, ReplayConnector},
+    chord::{Chord, ChordRegistry, ChordTrigger},
+    config::SpeedEditorConfig,
+    controller::{Controller, SpeedEditorState},
+    device_info::DeviceInfo,
+    event::Event,
+    gesture::{Gesture, GestureConfig, GestureDetector},
     handler::{ConnectedHandler, Handler},
+    jog::{AccelCurve, JogConfig, JogMotion, JogProcessor},
+    jog_led::JogLed,
+    jog_mode::JogMode,
+    jog_tracker::JogTracker,
     key::Key,
     key_led::KeyLed,
-    SpeedEditor, SpeedEditorError,
+    layer::{Layer, LayerStack, LayerSwitch, BASE_LAYER},
+    led_effect::{EffectId, LedEffect, LedEffects, CAM_LEDS},
+    manager::{DeviceId, DeviceManager},
+    mock::{MockConnector, MockTransport},
+    stop_token::StopToken,
+    transport::{Connector, Transport},
+    Events, SpeedEditor, SpeedEditorError,
 };
 
+#[cfg(feature = "hidapi")]
 pub fn new() -> Result<SpeedEditor, SpeedEditorError> {
+    with_connector(HidConnector::new())
+}
+
+// Open the unit with the given serial number, see list_devices()
+#[cfg(feature = "hidapi")]
+pub fn open_serial(serial: &str) -> Result<SpeedEditor, SpeedEditorError> {
+    with_connector(HidConnector::serial(serial))
+}
+
+#[cfg(feature = "hidapi")]
+pub fn open_path(path: &str) -> Result<SpeedEditor, SpeedEditorError> {
+    with_connector(HidConnector::path(path))
+}
+
+pub fn with_connector<C>(connector: C) -> Result<SpeedEditor, SpeedEditorError>
+where
+    C: Connector + 'static,
+{
     Ok(SpeedEditor {
+        config: SpeedEditorConfig::default(),
+        connector: Box::new(connector),
         device: None,
         last_authenticated_at: None,
+        auth_failures: 0,
         current_keys: Vec::default(),
+        current_unknown_keys: Vec::default(),
         current_key_leds: Vec::default(),
+        led_effects: LedEffects::new(),
+        written_key_leds: None,
+        current_jog_mode: JogMode::Relative,
+        current_jog_leds: Vec::default(),
+        jog_leds_follow_mode: false,
+        battery: None,
+        pending_events: VecDeque::default(),
+        clear_leds_on_drop: false,
+        gestures: None,
+        chords: ChordRegistry::new(),
+        layers: LayerStack::new(),
+        jog: None,
+        jog_tracker: None,
         connected_handler: Handler::new(),
         disconnected_handler: Handler::new(),
         keys_handler: Handler::new(),
         key_handler: Handler::new(),
         key_down_handler: Handler::new(),
         key_up_handler: Handler::new(),
+        unknown_key_handler: Handler::new(),
+        chord_handler: Handler::new(),
+        layer_changed_handler: Handler::new(),
+        gesture_handler: Handler::new(),
         jog_handler: Handler::new(),
+        jog_motion_handler: Handler::new(),
+        shuttle_speed_handler: Handler::new(),
+        step_handler: Handler::new(),
+        battery_handler: Handler::new(),
         unknown_handler: Handler::new(),
     })
 }
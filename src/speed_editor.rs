@@ -1,31 +1,109 @@
+#[cfg(feature = "tokio")]
+pub mod async_speed_editor;
+pub mod auth;
+pub mod battery;
+pub mod capture;
+pub mod chord;
+pub(crate) mod command;
+pub mod config;
+pub mod controller;
+pub mod device_info;
+pub mod event;
+pub mod gesture;
 pub mod handler;
+pub mod jog;
+pub mod jog_led;
+pub mod jog_mode;
+pub mod jog_tracker;
 pub mod key;
 pub mod key_led;
+pub mod layer;
+pub mod led_effect;
+pub mod manager;
+#[cfg(feature = "midi")]
+pub mod midi;
+pub mod mock;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "osc")]
+pub mod osc;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod stop_token;
+pub mod transport;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 use chrono::{DateTime, Utc};
-use hidapi::{HidDevice, HidError};
-use std::{io::Read, thread, time::Duration};
+use std::{
+    collections::VecDeque,
+    io::Read,
+    thread,
+    time::{Duration, Instant},
+};
 use strum::IntoEnumIterator;
 
+use battery::BatteryStatus;
+use chord::{ChordOutput, ChordRegistry};
+use config::SpeedEditorConfig;
+use event::Event;
+use gesture::{Gesture, GestureConfig, GestureDetector};
 use handler::{
-    ConnectedHandler, DisconnectedHandler, JogHandler, KeyDownHandler, KeyHandler, KeyUpHandler,
-    KeysHandler, UnknownHandler,
+    BatteryHandler, ChordHandler, ConnectedHandler, DisconnectedHandler, GestureHandler,
+    JogHandler, JogMotionHandler, KeyDownHandler, KeyHandler, KeyUpHandler, KeysHandler,
+    LayerChangedHandler, ShuttleSpeedHandler, StepHandler, UnknownHandler, UnknownKeyHandler,
 };
+use jog::{JogConfig, JogMotion, JogProcessor};
+use jog_led::JogLed;
+use jog_mode::JogMode;
+use jog_tracker::JogTracker;
 use key::Key;
 use key_led::KeyLed;
+use layer::LayerStack;
+use led_effect::{EffectId, LedEffect, LedEffects};
+use stop_token::StopToken;
+#[cfg(feature = "hidapi")]
+use transport::HidConnector;
+use transport::{Connector, Transport};
 
 pub struct SpeedEditor {
-    pub device: Option<HidDevice>,
+    pub config: SpeedEditorConfig,
+    pub connector: Box<dyn Connector>,
+    pub device: Option<Box<dyn Transport>>,
     pub last_authenticated_at: Option<DateTime<Utc>>,
+    pub auth_failures: u32,
     pub current_keys: Vec<Key>,
+    pub current_unknown_keys: Vec<u8>,
     pub current_key_leds: Vec<KeyLed>,
+    pub led_effects: LedEffects,
+    // Key LED mask last written to the device
+    pub written_key_leds: Option<i32>,
+    pub current_jog_mode: JogMode,
+    pub current_jog_leds: Vec<JogLed>,
+    pub jog_leds_follow_mode: bool,
+    pub battery: Option<BatteryStatus>,
+    pub pending_events: VecDeque<Event>,
+    pub clear_leds_on_drop: bool,
+    pub gestures: Option<GestureDetector>,
+    pub chords: ChordRegistry,
+    pub layers: LayerStack,
+    pub jog: Option<JogProcessor>,
+    pub jog_tracker: Option<JogTracker>,
     pub connected_handler: ConnectedHandler,
     pub disconnected_handler: DisconnectedHandler,
     pub keys_handler: KeysHandler,
     pub key_handler: KeyHandler,
     pub key_down_handler: KeyDownHandler,
     pub key_up_handler: KeyUpHandler,
+    pub unknown_key_handler: UnknownKeyHandler,
+    pub chord_handler: ChordHandler,
+    pub layer_changed_handler: LayerChangedHandler,
+    pub gesture_handler: GestureHandler,
     pub jog_handler: JogHandler,
+    pub jog_motion_handler: JogMotionHandler,
+    pub shuttle_speed_handler: ShuttleSpeedHandler,
+    pub step_handler: StepHandler,
+    pub battery_handler: BatteryHandler,
     pub unknown_handler: UnknownHandler,
 }
 
@@ -33,16 +111,22 @@ pub type SpeedEditorResult = Result<(), SpeedEditorError>;
 
 #[derive(Debug)]
 pub enum SpeedEditorError {
+    #[cfg(feature = "hidapi")]
     HidApiError(hidapi::HidError),
     StdIoError(std::io::Error),
     AuthGetKbdChallengeError,
     AuthGetKbdResponseError,
     AuthGetKbdStatusError,
     CallbackExecutionError,
+    CaptureParseError(String),
+    // Names the offending entry of a keymap profile
+    ProfileError(String),
+    Stopped,
 }
 
+#[cfg(feature = "hidapi")]
 impl From<hidapi::HidError> for SpeedEditorError {
-    fn from(e: HidError) -> Self {
+    fn from(e: hidapi::HidError) -> Self {
         SpeedEditorError::HidApiError(e)
     }
 }
@@ -54,167 +138,290 @@ impl From<std::io::Error> for SpeedEditorError {
 }
 
 impl SpeedEditor {
-    const VID: u16 = 7899;
-    const PID: u16 = 55822;
-    const READ_TIMEOUT: i32 = 1000;
-    const RECONNECT_INTERVAL: u64 = 100;
-    const AUTH_INTERVAL: i64 = 30000;
-
-    const AUTH_EVEN_TBL: [u64; 8] = [
-        4242707987619187656,
-        3069963097229903046,
-        2352841328256802570,
-        12646368222702737177,
-        17018789593460232529,
-        12706253227766860309,
-        11978781369061872007,
-        8438608961089703390,
-    ];
-
-    const AUTH_ODD_TBL: [u64; 8] = [
-        4477338132788707294,
-        2622620659002747676,
-        11637077509869926595,
-        7923852755392722584,
-        8224257920127642516,
-        4049197610885016386,
-        18266591397768539273,
-        7035737829027231430,
-    ];
-
-    const MASK: u64 = 12077075256910773232;
-
-    fn rol8(&self, v: u64) -> u64 {
-        ((v << 56) | (v >> 8)) & 18446744073709551615
-    }
-
-    fn rol8n(&self, mut v: u64, n: u64) -> u64 {
-        for _ in 0..n {
-            v = self.rol8(v);
-        }
-        v
-    }
-
-    /*
-     * Authenticate module is taken from:
-     * https://github.com/smunaut/blackmagic-misc
-     * Copyright (C) 2021 Sylvain Munaut <tnt@246tNt.com>
-     *
-     * */
-    fn auth(&mut self) -> SpeedEditorResult {
-        let mut buf = [0; 8];
-        let mut bytes = vec![0; 10];
+    pub fn set_config(&mut self, config: SpeedEditorConfig) {
+        self.config = config;
+    }
 
+    // A failed handshake is retried with backoff, then the device is reset
+    // and reopened, so auth errors never end the run loop.
+    fn auth(&mut self) -> SpeedEditorResult {
         if let Some(device) = &self.device {
-            device.send_feature_report(&[0x6, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0])?;
-            bytes[0] = 0x6;
-
-            let _ = device.get_feature_report(&mut bytes)?;
-            if bytes[0] != 0x6 || bytes[1] != 0x0 {
-                return Err(SpeedEditorError::AuthGetKbdChallengeError);
+            match auth::authenticate(device.as_ref()) {
+                Ok(()) => {
+                    let connected = self.last_authenticated_at.is_none();
+                    self.auth_failures = 0;
+                    self.last_authenticated_at = Some(Utc::now());
+
+                    // The panel comes back dark after a reconnect
+                    if connected && self.restore_outputs().is_err() {
+                        self.disconnect()?;
+                    }
+                }
+                Err(_) if self.auth_failures < self.config.auth_retries => {
+                    self.auth_failures += 1;
+                    thread::sleep(self.config.auth_backoff_for(self.auth_failures));
+                }
+                Err(_) => {
+                    self.auth_failures = 0;
+                    self.disconnect()?;
+                }
             }
+        }
 
-            (&bytes[2..]).read_exact(&mut buf).unwrap();
-            let challenge = u64::from_le_bytes(buf);
+        Ok(())
+    }
 
-            device.send_feature_report(&[0x6, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0])?;
-            let _ = device.get_feature_report(&mut bytes)?;
-            if bytes[0] != 0x6 || bytes[1] != 0x2 {
-                return Err(SpeedEditorError::AuthGetKbdResponseError);
-            }
+    fn is_expired(&self) -> bool {
+        if let Some(at) = self.last_authenticated_at {
+            let elapsed_time = Utc::now() - at;
+            elapsed_time.num_milliseconds() >= self.config.auth_interval.as_millis() as i64
+        } else {
+            true
+        }
+    }
 
-            let n = challenge & 7;
-            let mut v = self.rol8n(challenge, n);
-            let k: u64;
-            if (v & 1) == ((120 >> n) & 1) {
-                k = Self::AUTH_EVEN_TBL[n as usize];
-            } else {
-                v = v ^ self.rol8(v);
-                k = Self::AUTH_ODD_TBL[n as usize];
-            }
+    pub fn run(&mut self) -> SpeedEditorResult {
+        self.run_until(&StopToken::new())
+    }
 
-            let response = v ^ (self.rol8(v) & Self::MASK) ^ k;
-            buf = response.to_le_bytes();
+    // Like run(), returning once `token` is stopped. The token is checked
+    // between reads, so it may take up to the read timeout to return.
+    pub fn run_until(&mut self, token: &StopToken) -> SpeedEditorResult {
+        while !token.is_stopped() {
+            self.step(self.config.read_timeout_ms())?;
 
-            bytes[1] = 0x3;
-            for i in 0..8 {
-                bytes[i + 2] = buf[i];
+            while let Some(event) = self.pending_events.pop_front() {
+                self.dispatch(&event)?;
             }
+        }
 
-            device.send_feature_report(bytes.as_slice())?;
+        Ok(())
+    }
 
-            let _ = device.get_feature_report(&mut bytes)?;
-            if bytes[0] != 0x6 || bytes[1] != 0x4 {
-                return Err(SpeedEditorError::AuthGetKbdStatusError);
+    // Release the device, turning all LEDs off first if clear_leds_on_drop is set
+    pub fn close(&mut self) -> SpeedEditorResult {
+        if self.clear_leds_on_drop {
+            if let Some(device) = &self.device {
+                device.write(&[0x2, 0x0, 0x0, 0x0, 0x0, 0x0])?;
+                device.write(&[0x4, 0x0])?;
             }
-
-            self.last_authenticated_at = Some(Utc::now());
         }
 
+        self.device = None;
+        self.last_authenticated_at = None;
         Ok(())
     }
 
-    fn is_expired(&self) -> bool {
-        if let Some(at) = self.last_authenticated_at {
-            let elapsed_time = Utc::now() - at;
-            elapsed_time.num_milliseconds() >= Self::AUTH_INTERVAL
-        } else {
-            true
-        }
+    pub fn set_clear_leds_on_drop(&mut self, clear: bool) {
+        self.clear_leds_on_drop = clear;
     }
 
-    pub fn run(&mut self) -> SpeedEditorResult {
+    // Wait up to `timeout` for the next event. Callbacks are called before it is returned.
+    pub fn poll_event(&mut self, timeout: Duration) -> Result<Option<Event>, SpeedEditorError> {
+        let deadline = Instant::now() + timeout;
+
         loop {
-            if self.device.is_none() {
-                self.connect()?;
-                continue;
+            if let Some(event) = self.pending_events.pop_front() {
+                self.dispatch(&event)?;
+                return Ok(Some(event));
             }
 
-            if self.is_expired() {
-                self.auth()?;
-                continue;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let timeout = remaining.min(self.config.read_timeout).as_millis() as i32;
+            self.step(timeout)?;
+
+            if self.pending_events.is_empty() && Instant::now() >= deadline {
+                return Ok(None);
             }
+        }
+    }
 
-            if let Some(device) = &self.device {
-                let mut buf = [0; 64];
-                match device.read_timeout(&mut buf, Self::READ_TIMEOUT) {
-                    Ok(len) => {
-                        if len > 0 {
-                            self.process_events(&buf[..len])?;
-                        }
+    pub fn events(&mut self) -> Events<'_> {
+        Events { speed_editor: self }
+    }
+
+    // One pass of connect, authenticate or read
+    fn step(&mut self, timeout: i32) -> SpeedEditorResult {
+        if self.device.is_none() {
+            return self.connect();
+        }
+
+        if self.is_expired() {
+            return self.auth();
+        }
+
+        let timeout = self.timer_timeout(timeout);
+        if let Some(device) = &self.device {
+            let mut buf = [0; 64];
+            match device.read_timeout(&mut buf, timeout) {
+                Ok(len) => {
+                    if len > 0 {
+                        self.process_events(&buf[..len])?;
                     }
-                    Err(_) => self.disconnect()?,
                 }
+                Err(_) => self.disconnect()?,
+            }
+        }
+
+        self.tick_timers()
+    }
+
+    // Shorten a read timeout so that timers such as gestures fire on time
+    fn timer_timeout(&self, timeout: i32) -> i32 {
+        let gestures = self.gestures.as_ref().and_then(|g| g.next_deadline());
+        let effects = self.led_effects.next_deadline(Instant::now());
+        match gestures.into_iter().chain(effects).min() {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                timeout.min(remaining.as_micros().div_ceil(1000) as i32)
+            }
+            None => timeout,
+        }
+    }
+
+    fn tick_timers(&mut self) -> SpeedEditorResult {
+        if let Some(gestures) = self.gestures.as_mut() {
+            for gesture in gestures.tick(Instant::now()) {
+                self.emit(Event::Gesture(gesture));
+            }
+        }
+
+        if !self.led_effects.is_empty() {
+            self.led_effects.expire(Instant::now());
+            // Only write when an effect changed what is lit
+            if self.written_key_leds != Some(self.key_led_mask()) && self.light_key_leds().is_err()
+            {
+                self.disconnect()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Report gestures with the given thresholds, see Gesture
+    pub fn enable_gestures(&mut self, config: GestureConfig) {
+        self.gestures = Some(GestureDetector::new(config));
+    }
+
+    pub fn disable_gestures(&mut self) {
+        self.gestures = None;
+    }
+
+    // Report JogMotion and ShuttleSpeed events in addition to Jog
+    pub fn enable_jog_processing(&mut self, config: JogConfig) {
+        self.jog = Some(JogProcessor::new(config));
+    }
+
+    pub fn disable_jog_processing(&mut self) {
+        self.jog = None;
+    }
+
+    // Report Step events, see JogTracker
+    pub fn enable_jog_tracking(&mut self, tracker: JogTracker) {
+        self.jog_tracker = Some(tracker);
+    }
+
+    pub fn disable_jog_tracking(&mut self) {
+        self.jog_tracker = None;
+    }
+
+    pub fn jog_position(&self) -> Option<i64> {
+        self.jog_tracker.as_ref().map(|t| t.position())
+    }
+
+    pub fn reset_jog_origin(&mut self) {
+        if let Some(tracker) = self.jog_tracker.as_mut() {
+            tracker.reset();
+        }
+    }
+
+    fn dispatch(&mut self, event: &Event) -> SpeedEditorResult {
+        match event {
+            Event::Connected => self.connected_handler.call(),
+            Event::Disconnected => self.disconnected_handler.call(),
+            Event::KeyDown(key) => {
+                self.key_handler.call(*key, true)?;
+                self.key_down_handler.call(*key)
+            }
+            Event::KeyUp(key) => {
+                self.key_handler.call(*key, false)?;
+                self.key_up_handler.call(*key)
             }
+            Event::UnknownKey(code, down) => self.unknown_key_handler.call(*code, *down),
+            Event::Keys(keys) => self.keys_handler.call(keys),
+            Event::Chord(keys) => self.chord_handler.call(keys),
+            Event::LayerChanged(name) => self.layer_changed_handler.call(name),
+            Event::Gesture(gesture) => self.gesture_handler.call(*gesture),
+            Event::Jog(mode, value) => self.jog_handler.call(*mode, *value),
+            Event::JogMotion(motion) => self.jog_motion_handler.call(*motion),
+            Event::ShuttleSpeed(speed) => self.shuttle_speed_handler.call(*speed),
+            Event::Step(direction) => self.step_handler.call(*direction),
+            Event::Battery(status) => self.battery_handler.call(*status),
+            Event::Unknown(data) => self.unknown_handler.call(data),
         }
     }
 
+    fn emit(&mut self, event: Event) {
+        self.pending_events.push_back(event);
+    }
+
     fn process_events(&mut self, buf: &[u8]) -> SpeedEditorResult {
         match buf[0] {
-            3 => self.jog_event(buf[1], &buf[2..])?,
+            3 => self.jog_event(buf)?,
             4 => self.key_event(&buf[1..])?,
+            7 => self.battery_event(buf)?,
             _ => self.unknown_event(buf)?,
         }
 
         Ok(())
     }
 
-    fn jog_event(&mut self, mode: u8, buf: &[u8]) -> SpeedEditorResult {
+    fn jog_event(&mut self, buf: &[u8]) -> SpeedEditorResult {
+        let mode = match buf.get(1).map(|&m| JogMode::try_from(m)) {
+            Some(Ok(mode)) => mode,
+            _ => return self.unknown_event(buf),
+        };
+
         let mut data = [0; 4];
-        (&buf[..]).read_exact(&mut data)?;
-        let value = i32::from_le_bytes(data) / 360;
-        self.jog_handler.call(mode, value)?;
+        (&buf[2..]).read_exact(&mut data)?;
+        let raw = i32::from_le_bytes(data);
+
+        // Relative modes report a delta, absolute modes the position since the mode was set
+        let value = if mode.is_absolute() { raw } else { raw / 360 };
+
+        self.current_jog_mode = mode;
+        self.emit(Event::Jog(mode, value));
+
+        if let Some(jog) = self.jog.as_mut() {
+            let (motion, shuttle_speed) = jog.process(mode, raw, Instant::now());
+            self.emit(Event::JogMotion(motion));
+            if let Some(speed) = shuttle_speed {
+                self.emit(Event::ShuttleSpeed(speed));
+            }
+        }
+
+        if let Some(tracker) = self.jog_tracker.as_mut() {
+            let steps = tracker.feed(mode, raw);
+            for _ in 0..steps.unsigned_abs() {
+                self.emit(Event::Step(steps.signum() as i32));
+            }
+        }
         Ok(())
     }
 
     fn key_event(&mut self, buf: &[u8]) -> SpeedEditorResult {
-        let current_keys: Vec<Key> = buf
-            .iter()
-            .enumerate()
-            .filter(|&(i, _)| i % 2 == 0)
-            .filter(|&(_, &v)| v > 0)
-            .map(|(_, &v)| Key::try_from(v).unwrap())
-            .collect();
+        let mut current_keys: Vec<Key> = vec![];
+        let mut unknown_keys: Vec<u8> = vec![];
+        for &v in buf.iter().step_by(2).filter(|&&v| v > 0) {
+            // Codes missing from Key (other firmware or devices) are reported separately
+            match Key::try_from(v) {
+                Ok(key) => current_keys.push(key),
+                Err(_) => unknown_keys.push(v),
+            }
+        }
+
+        if unknown_keys != self.current_unknown_keys {
+            self.unknown_key_event(unknown_keys);
+        }
 
         // Are you pressing 7 or more keys at the same time?
         if current_keys == self.current_keys {
@@ -224,7 +431,7 @@ impl SpeedEditor {
         let down_keys: Vec<Key> = current_keys
             .iter()
             .map(|&v| {
-                if self.current_keys.iter().find(|&k| *k == v) == None {
+                if !self.current_keys.contains(&v) {
                     v
                 } else {
                     Key::None
@@ -237,7 +444,7 @@ impl SpeedEditor {
             .current_keys
             .iter()
             .map(|&v| {
-                if current_keys.iter().find(|&k| *k == v) == None {
+                if !current_keys.contains(&v) {
                     v
                 } else {
                     Key::None
@@ -249,41 +456,130 @@ impl SpeedEditor {
         self.current_keys = current_keys.to_owned();
 
         for k in down_keys {
-            self.key_handler.call(k, true)?;
-            self.key_down_handler.call(k)?;
+            let layer = self.layers.active_index();
+            if self.layers.key_down(k) {
+                self.layer_changed(layer)?;
+                continue;
+            }
+            let output = self.chords.key_down(k);
+            self.chord_output(output);
         }
 
         for k in up_keys {
-            self.key_handler.call(k, false)?;
-            self.key_up_handler.call(k)?;
+            let layer = self.layers.active_index();
+            if self.layers.key_up(k) {
+                self.layer_changed(layer)?;
+                continue;
+            }
+            let output = self.chords.key_up(k);
+            self.chord_output(output);
+        }
+
+        self.emit(Event::Keys(self.current_keys.clone()));
+
+        Ok(())
+    }
+
+    // Swap the LED presets and report the new layer, if it changed
+    fn layer_changed(&mut self, previous: usize) -> SpeedEditorResult {
+        let active = self.layers.active_index();
+        if active == previous {
+            return Ok(());
         }
 
-        self.keys_handler.call(&self.current_keys)?;
+        let off = self.layers.layer(previous).leds.clone();
+        if !off.is_empty() {
+            self.set_leds(off, false)?;
+        }
+        let on = self.layers.layer(active).leds.clone();
+        if !on.is_empty() {
+            self.set_leds(on, true)?;
+        }
 
+        self.emit(Event::LayerChanged(self.layers.active().name.clone()));
         Ok(())
     }
 
+    fn chord_output(&mut self, output: Vec<ChordOutput>) {
+        for o in output {
+            match o {
+                ChordOutput::Key(key, down) => self.key_transition(key, down),
+                ChordOutput::Chord(keys) => self.emit(Event::Chord(keys)),
+            }
+        }
+    }
+
+    fn key_transition(&mut self, key: Key, down: bool) {
+        let now = Instant::now();
+
+        if down {
+            self.emit(Event::KeyDown(key));
+        } else {
+            self.emit(Event::KeyUp(key));
+        }
+
+        if let Some(gestures) = self.gestures.as_mut() {
+            let found = if down {
+                gestures.key_down(key, now)
+            } else {
+                gestures.key_up(key, now)
+            };
+            for gesture in found {
+                self.emit(Event::Gesture(gesture));
+            }
+        }
+    }
+
+    fn unknown_key_event(&mut self, unknown_keys: Vec<u8>) {
+        let previous = std::mem::replace(&mut self.current_unknown_keys, unknown_keys.clone());
+
+        for &code in unknown_keys.iter().filter(|c| !previous.contains(c)) {
+            self.emit(Event::UnknownKey(code, true));
+        }
+
+        for &code in previous.iter().filter(|c| !unknown_keys.contains(c)) {
+            self.emit(Event::UnknownKey(code, false));
+        }
+    }
+
+    fn battery_event(&mut self, buf: &[u8]) -> SpeedEditorResult {
+        match BatteryStatus::from_report(buf) {
+            Some(status) => {
+                self.battery = Some(status);
+                self.emit(Event::Battery(status));
+                Ok(())
+            }
+            None => self.unknown_event(buf),
+        }
+    }
+
+    pub fn battery_status(&self) -> Option<BatteryStatus> {
+        self.battery
+    }
+
     fn unknown_event(&mut self, buf: &[u8]) -> SpeedEditorResult {
-        self.unknown_handler.call(buf)
+        self.emit(Event::Unknown(buf.to_vec()));
+        Ok(())
     }
 
     fn disconnect(&mut self) -> SpeedEditorResult {
         self.device = None;
         self.last_authenticated_at = None;
-        self.disconnected_handler.call()
+        self.battery = None;
+        self.written_key_leds = None;
+        self.emit(Event::Disconnected);
+        Ok(())
     }
 
     // Try to connect
     fn connect(&mut self) -> SpeedEditorResult {
-        let api = hidapi::HidApi::new()?;
-
-        self.device = match api.open(SpeedEditor::VID, SpeedEditor::PID) {
-            Ok(device) => {
-                self.connected_handler.call()?;
+        self.device = match self.connector.open()? {
+            Some(device) => {
+                self.emit(Event::Connected);
                 Some(device)
             }
-            Err(_) => {
-                thread::sleep(Duration::from_millis(Self::RECONNECT_INTERVAL));
+            None => {
+                thread::sleep(self.config.reconnect_interval);
                 None
             }
         };
@@ -300,7 +596,7 @@ impl SpeedEditor {
             .current_key_leds
             .iter()
             .filter(|&i| *i != led)
-            .map(|i| *i)
+            .copied()
             .collect::<Vec<KeyLed>>();
     }
 
@@ -338,21 +634,129 @@ impl SpeedEditor {
         self.light_key_leds()
     }
 
-    fn light_key_leds(&mut self) -> SpeedEditorResult {
+    // Run an effect on top of the static key LEDs, see LedEffect
+    pub fn add_led_effect(&mut self, effect: LedEffect) -> Result<EffectId, SpeedEditorError> {
+        let id = self.led_effects.add(effect, Instant::now());
+        self.light_key_leds()?;
+        Ok(id)
+    }
+
+    pub fn remove_led_effect(&mut self, id: EffectId) -> SpeedEditorResult {
+        self.led_effects.remove(id);
+        self.light_key_leds()
+    }
+
+    pub fn clear_led_effects(&mut self) -> SpeedEditorResult {
+        self.led_effects.clear();
+        self.light_key_leds()
+    }
+
+    pub fn set_jog_mode(&mut self, mode: JogMode) -> SpeedEditorResult {
+        self.current_jog_mode = mode;
+        self.write_jog_mode()?;
+
+        if self.jog_leds_follow_mode {
+            self.follow_jog_mode()?;
+        }
+        Ok(())
+    }
+
+    fn write_jog_mode(&mut self) -> SpeedEditorResult {
         if let Some(device) = &self.device {
-            let mut leds: i32 = 0;
-            for i in self.current_key_leds.iter() {
-                leds |= 1 << *i as i32;
+            let mode = self.current_jog_mode as u8;
+            device.write(&[0x3, mode, 0x0, 0x0, 0x0, 0x0, 0xff])?;
+        }
+        Ok(())
+    }
+
+    // Send the jog mode and the LEDs the panel should show
+    fn restore_outputs(&mut self) -> SpeedEditorResult {
+        self.write_jog_mode()?;
+        self.light_key_leds()?;
+        self.light_jog_leds()
+    }
+
+    fn add_jog_led(&mut self, led: JogLed) {
+        if !self.current_jog_leds.contains(&led) {
+            self.current_jog_leds.push(led);
+        }
+    }
+
+    fn remove_jog_led(&mut self, led: JogLed) {
+        self.current_jog_leds.retain(|&i| i != led);
+    }
+
+    pub fn set_all_jog_leds(&mut self, on: bool) -> SpeedEditorResult {
+        for led in JogLed::iter() {
+            if on {
+                self.add_jog_led(led);
+            } else {
+                self.remove_jog_led(led);
             }
+        }
+
+        self.light_jog_leds()
+    }
+
+    pub fn set_jog_led(&mut self, led: JogLed, on: bool) -> SpeedEditorResult {
+        if on {
+            self.add_jog_led(led);
+        } else {
+            self.remove_jog_led(led);
+        }
+
+        self.light_jog_leds()
+    }
+
+    // Light only the indicator of the active jog mode, now and on every set_jog_mode()
+    pub fn set_jog_leds_follow_mode(&mut self, follow: bool) -> SpeedEditorResult {
+        self.jog_leds_follow_mode = follow;
+
+        if follow {
+            self.follow_jog_mode()?;
+        }
+        Ok(())
+    }
+
+    fn follow_jog_mode(&mut self) -> SpeedEditorResult {
+        self.current_jog_leds = vec![JogLed::for_mode(self.current_jog_mode)];
+        self.light_jog_leds()
+    }
+
+    fn light_jog_leds(&mut self) -> SpeedEditorResult {
+        if let Some(device) = &self.device {
+            let mut leds: u8 = 0;
+            for i in self.current_jog_leds.iter() {
+                leds |= 1 << *i as u8;
+            }
+
+            device.write(&[0x4, leds])?;
+        }
+        Ok(())
+    }
+
+    fn key_led_mask(&self) -> i32 {
+        let mut leds: i32 = 0;
+        for i in self
+            .led_effects
+            .apply(&self.current_key_leds, Instant::now())
+            .iter()
+        {
+            leds |= 1 << *i as i32;
+        }
+        leds
+    }
+
+    fn light_key_leds(&mut self) -> SpeedEditorResult {
+        if let Some(device) = &self.device {
+            let leds = self.key_led_mask();
 
             let buf = leds.to_le_bytes();
             let mut data = [0x2, 0x0, 0x0, 0x0, 0x0, 0x0];
-            data[0] = 0x2;
-            for i in 0..4 {
-                data[i + 1] = buf[i];
-            }
+            data[1..5].copy_from_slice(&buf);
 
             device.write(data.as_slice())?;
+            self.written_key_leds = Some(leds);
         }
         Ok(())
     }
@@ -399,13 +803,73 @@ impl SpeedEditor {
         self.key_up_handler.callbacks.push(Box::new(callback));
     }
 
+    pub fn on_unknown_key<F>(&mut self, callback: F)
+    where
+        F: FnMut(u8, bool) -> SpeedEditorResult + Sync + Send + 'static,
+    {
+        self.unknown_key_handler.callbacks.push(Box::new(callback));
+    }
+
+    pub fn on_chord<F>(&mut self, callback: F)
+    where
+        F: FnMut(Vec<Key>) -> SpeedEditorResult + Sync + Send + 'static,
+    {
+        self.chord_handler.callbacks.push(Box::new(callback));
+    }
+
+    pub fn on_layer_changed<F>(&mut self, callback: F)
+    where
+        F: FnMut(String) -> SpeedEditorResult + Sync + Send + 'static,
+    {
+        self.layer_changed_handler
+            .callbacks
+            .push(Box::new(callback));
+    }
+
+    pub fn on_gesture<F>(&mut self, callback: F)
+    where
+        F: FnMut(Gesture) -> SpeedEditorResult + Sync + Send + 'static,
+    {
+        self.gesture_handler.callbacks.push(Box::new(callback));
+    }
+
     pub fn on_jog<F>(&mut self, callback: F)
     where
-        F: FnMut(u8, i32) -> SpeedEditorResult + Sync + Send + 'static,
+        F: FnMut(JogMode, i32) -> SpeedEditorResult + Sync + Send + 'static,
     {
         self.jog_handler.callbacks.push(Box::new(callback));
     }
 
+    pub fn on_jog_motion<F>(&mut self, callback: F)
+    where
+        F: FnMut(JogMotion) -> SpeedEditorResult + Sync + Send + 'static,
+    {
+        self.jog_motion_handler.callbacks.push(Box::new(callback));
+    }
+
+    pub fn on_shuttle_speed<F>(&mut self, callback: F)
+    where
+        F: FnMut(f32) -> SpeedEditorResult + Sync + Send + 'static,
+    {
+        self.shuttle_speed_handler
+            .callbacks
+            .push(Box::new(callback));
+    }
+
+    pub fn on_step<F>(&mut self, callback: F)
+    where
+        F: FnMut(i32) -> SpeedEditorResult + Sync + Send + 'static,
+    {
+        self.step_handler.callbacks.push(Box::new(callback));
+    }
+
+    pub fn on_battery<F>(&mut self, callback: F)
+    where
+        F: FnMut(BatteryStatus) -> SpeedEditorResult + Sync + Send + 'static,
+    {
+        self.battery_handler.callbacks.push(Box::new(callback));
+    }
+
     pub fn on_unknown<F>(&mut self, callback: F)
     where
         F: FnMut(&[u8]) -> SpeedEditorResult + Sync + Send + 'static,
@@ -414,11 +878,508 @@ impl SpeedEditor {
     }
 }
 
+impl Drop for SpeedEditor {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+pub struct Events<'a> {
+    speed_editor: &'a mut SpeedEditor,
+}
+
+impl Iterator for Events<'_> {
+    type Item = Result<Event, SpeedEditorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let timeout = self.speed_editor.config.read_timeout;
+            match self.speed_editor.poll_event(timeout) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{
+        BatteryStatus, Event, Gesture, GestureConfig, JogConfig, JogLed, JogMode, JogTracker, Key,
+        KeyLed, LedEffect, SpeedEditor, SpeedEditorConfig, SpeedEditorError, StopToken,
+    };
+    use crate::{speed_editor::mock::fixtures::authenticated, MockConnector, MockTransport};
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    fn drain(se: &mut SpeedEditor) -> Vec<Event> {
+        let mut events = vec![];
+        while let Some(event) = se.poll_event(Duration::ZERO).unwrap() {
+            events.push(event);
+        }
+        events
+    }
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn run_with_mock_transport() {
+        let transport = MockTransport::new();
+        transport.push_auth_handshake();
+        transport.push_input_report(&[0x4, 0x7, 0x0, 0x0, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0, 0x0, 0x0]);
+        transport.unplug();
+
+        let connector = MockConnector::new();
+        connector.push(transport.clone());
+
+        let keys = Arc::new(Mutex::new(vec![]));
+        let mut se = crate::with_connector(connector).unwrap();
+        let k = keys.clone();
+        se.on_key(move |key, down| {
+            k.lock().unwrap().push((key, down));
+            Ok(())
+        });
+        se.on_disconnected(|| Err(SpeedEditorError::CallbackExecutionError));
+
+        assert!(matches!(
+            se.run(),
+            Err(SpeedEditorError::CallbackExecutionError)
+        ));
+        assert_eq!(
+            *keys.lock().unwrap(),
+            vec![(Key::In, true), (Key::In, false)]
+        );
+
+        let sent = transport.sent_feature_reports();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[2][..2], [0x6, 0x3]);
+    }
+
+    #[test]
+    fn key_leds_are_written_to_transport() {
+        let transport = MockTransport::new();
+        let mut se = crate::with_connector(MockConnector::new()).unwrap();
+        se.device = Some(Box::new(transport.clone()));
+
+        se.set_key_led(KeyLed::Cut, true).unwrap();
+        se.set_key_led(KeyLed::Cam1, true).unwrap();
+
+        assert_eq!(
+            transport.written().last().unwrap(),
+            &vec![0x2, 0x2, 0x40, 0x0, 0x0, 0x0]
+        );
+    }
+
+    #[test]
+    fn jog_reports_are_decoded_by_mode() {
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x3, 0x0, 0x98, 0xfe, 0xff, 0xff, 0x0]);
+        transport.push_input_report(&[0x3, 0x1, 0x0, 0x10, 0x0, 0x0, 0x0]);
+
+        let jogs = Arc::new(Mutex::new(vec![]));
+        let mut se = authenticated(&transport);
+        let j = jogs.clone();
+        se.on_jog(move |mode, value| {
+            j.lock().unwrap().push((mode, value));
+            Ok(())
+        });
+
+        drain(&mut se);
+
+        assert_eq!(
+            *jogs.lock().unwrap(),
+            vec![(JogMode::Relative, -1), (JogMode::Absolute, 4096)]
+        );
+        assert_eq!(se.current_jog_mode, JogMode::Absolute);
+    }
+
+    #[test]
+    fn set_jog_mode_writes_report() {
+        let transport = MockTransport::new();
+        let mut se = crate::with_connector(MockConnector::new()).unwrap();
+        se.device = Some(Box::new(transport.clone()));
+
+        se.set_jog_mode(JogMode::AbsoluteDeadzone).unwrap();
+
+        assert_eq!(
+            transport.written(),
+            vec![vec![0x3, 0x3, 0x0, 0x0, 0x0, 0x0, 0xff]]
+        );
+    }
+
+    #[test]
+    fn jog_leds_follow_jog_mode() {
+        let transport = MockTransport::new();
+        let mut se = crate::with_connector(MockConnector::new()).unwrap();
+        se.device = Some(Box::new(transport.clone()));
+
+        se.set_jog_led(JogLed::Scrl, true).unwrap();
+        se.set_jog_leds_follow_mode(true).unwrap();
+        se.set_jog_mode(JogMode::Absolute).unwrap();
+
+        assert_eq!(se.current_jog_leds, vec![JogLed::Shtl]);
+        assert_eq!(
+            transport.written(),
+            vec![
+                vec![0x4, 0x4],
+                vec![0x4, 0x1],
+                vec![0x3, 0x1, 0x0, 0x0, 0x0, 0x0, 0xff],
+                vec![0x4, 0x2],
+            ]
+        );
+    }
+
+    #[test]
+    fn battery_report_is_decoded() {
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x7, 0x1, 0x42]);
+
+        let statuses = Arc::new(Mutex::new(vec![]));
+        let mut se = authenticated(&transport);
+        let b = statuses.clone();
+        se.on_battery(move |status| {
+            b.lock().unwrap().push(status);
+            Ok(())
+        });
+
+        drain(&mut se);
+
+        let status = BatteryStatus {
+            level_percent: 66,
+            charging: true,
+        };
+        assert_eq!(*statuses.lock().unwrap(), vec![status]);
+        assert_eq!(se.battery_status(), Some(status));
+    }
+
+    #[test]
+    fn poll_event_returns_events_in_order() {
+        let transport = MockTransport::new();
+        transport.push_auth_handshake();
+        transport.push_input_report(&[0x4, 0x7, 0x0, 0x8, 0x0]);
+        transport.push_input_report(&[0x4, 0x8, 0x0, 0x0, 0x0]);
+        transport.unplug();
+
+        let connector = MockConnector::new();
+        connector.push(transport);
+        let mut se = crate::with_connector(connector).unwrap();
+
+        let events: Vec<Event> = se
+            .events()
+            .map(|e| e.unwrap())
+            .take_while(|e| *e != Event::Disconnected)
+            .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Connected,
+                Event::KeyDown(Key::In),
+                Event::KeyDown(Key::Out),
+                Event::Keys(vec![Key::In, Key::Out]),
+                Event::KeyUp(Key::In),
+                Event::Keys(vec![Key::Out]),
+            ]
+        );
+    }
+
+    #[test]
+    fn poll_event_times_out_without_input() {
+        let transport = MockTransport::new();
+        let mut se = authenticated(&transport);
+
+        assert_eq!(se.poll_event(Duration::from_millis(1)).unwrap(), None);
+    }
+
+    #[test]
+    fn run_until_returns_when_stopped() {
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x4, 0x7, 0x0]);
+
+        let token = StopToken::new();
+        let mut se = authenticated(&transport);
+        let t = token.clone();
+        se.on_key_down(move |_| {
+            t.stop();
+            Ok(())
+        });
+
+        se.run_until(&token).unwrap();
+        assert_eq!(se.current_keys, vec![Key::In]);
+    }
+
+    #[test]
+    fn drop_clears_leds() {
+        let transport = MockTransport::new();
+        let mut se = authenticated(&transport);
+        se.set_key_led(KeyLed::Cut, true).unwrap();
+        se.set_clear_leds_on_drop(true);
+        drop(se);
+
+        assert_eq!(
+            transport.written(),
+            vec![
+                vec![0x2, 0x2, 0x0, 0x0, 0x0, 0x0],
+                vec![0x2, 0x0, 0x0, 0x0, 0x0, 0x0],
+                vec![0x4, 0x0],
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_key_codes_do_not_panic() {
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x4, 0x7, 0x0, 0xfe, 0x0]);
+        transport.push_input_report(&[0x4, 0x7, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0]);
+
+        let codes = Arc::new(Mutex::new(vec![]));
+        let mut se = authenticated(&transport);
+        let c = codes.clone();
+        se.on_unknown_key(move |code, down| {
+            c.lock().unwrap().push((code, down));
+            Ok(())
+        });
+
+        assert_eq!(
+            drain(&mut se),
+            vec![
+                Event::UnknownKey(0xfe, true),
+                Event::KeyDown(Key::In),
+                Event::Keys(vec![Key::In]),
+                Event::UnknownKey(0xfe, false),
+                Event::KeyUp(Key::In),
+                Event::Keys(vec![]),
+            ]
+        );
+        assert_eq!(*codes.lock().unwrap(), vec![(0xfe, true), (0xfe, false)]);
+    }
+
+    #[test]
+    fn auth_failure_is_retried() {
+        let transport = MockTransport::new();
+        transport.push_feature_report(&[0x6, 0x9, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0]);
+        transport.push_auth_handshake();
+        transport.push_input_report(&[0x4, 0x7, 0x0]);
+
+        let connector = MockConnector::new();
+        connector.push(transport);
+        let mut se = crate::with_connector(connector).unwrap();
+        se.set_config(SpeedEditorConfig::new().auth_backoff(Duration::from_millis(1)));
+
+        let events: Vec<Event> = se.events().take(2).map(|e| e.unwrap()).collect();
+        assert_eq!(events, vec![Event::Connected, Event::KeyDown(Key::In)]);
+        assert_eq!(se.auth_failures, 0);
+    }
+
+    #[test]
+    fn device_is_reset_when_auth_keeps_failing() {
+        let broken = MockTransport::new();
+        for _ in 0..2 {
+            broken.push_feature_report(&[0x6, 0x9, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0]);
+        }
+        let transport = MockTransport::new();
+        transport.push_auth_handshake();
+        transport.push_input_report(&[0x4, 0x7, 0x0]);
+
+        let connector = MockConnector::new();
+        connector.push(broken);
+        connector.push(transport);
+        let mut se = crate::with_connector(connector).unwrap();
+        se.set_config(
+            SpeedEditorConfig::new()
+                .auth_retries(1)
+                .auth_backoff(Duration::from_millis(1))
+                .reconnect_interval(Duration::from_millis(1)),
+        );
+
+        let events: Vec<Event> = se.events().take(4).map(|e| e.unwrap()).collect();
+        assert_eq!(
+            events,
+            vec![
+                Event::Connected,
+                Event::Disconnected,
+                Event::Connected,
+                Event::KeyDown(Key::In),
+            ]
+        );
+    }
+
+    #[test]
+    fn gestures_are_reported_as_events() {
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x4, 0x31, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0]);
+
+        let mut se = authenticated(&transport);
+        se.enable_gestures(GestureConfig::new().double_tap_window(Duration::from_millis(5)));
+
+        let events: Vec<Event> = se.events().take(5).map(|e| e.unwrap()).collect();
+        assert_eq!(events[4], Event::Gesture(Gesture::Tap(Key::Esc)));
+    }
+
+    #[test]
+    fn chords_suppress_individual_key_events() {
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x4, 0x1c, 0x0]);
+        transport.push_input_report(&[0x4, 0x1c, 0x0, 0xf, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0]);
+
+        let mut se = authenticated(&transport);
+        se.chords.add_modifier(Key::Shtl);
+        se.chords.add(&[Key::Shtl, Key::Cut]);
+
+        let events: Vec<Event> = se
+            .events()
+            .take(4)
+            .map(|e| e.unwrap())
+            .filter(|e| !matches!(e, Event::Keys(_)))
+            .collect();
+        assert_eq!(events, vec![Event::Chord(vec![Key::Shtl, Key::Cut])]);
+    }
+
+    #[test]
+    fn shuttle_position_is_reported_as_speed() {
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x3, 0x1, 0x0, 0x10, 0x0, 0x0, 0x0]);
+
+        let mut se = authenticated(&transport);
+        se.enable_jog_processing(JogConfig::new());
+
+        let events: Vec<Event> = se.events().take(3).map(|e| e.unwrap()).collect();
+        assert_eq!(events[0], Event::Jog(JogMode::Absolute, 4096));
+        assert!(matches!(events[1], Event::JogMotion(m) if m.delta == 4096.0));
+        assert_eq!(events[2], Event::ShuttleSpeed(32.0));
+    }
+
+    #[test]
+    fn jog_tracker_reports_steps() {
+        let transport = MockTransport::new();
+        // -360 and +1080 in the relative mode
+        transport.push_input_report(&[0x3, 0x0, 0x98, 0xfe, 0xff, 0xff, 0x0]);
+        transport.push_input_report(&[0x3, 0x0, 0x38, 0x4, 0x0, 0x0, 0x0]);
+
+        let mut se = authenticated(&transport);
+        se.enable_jog_tracking(JogTracker::new(720));
+
+        let steps: Vec<Event> = se
+            .events()
+            .take(4)
+            .map(|e| e.unwrap())
+            .filter(|e| matches!(e, Event::Step(_)))
+            .collect();
+        assert_eq!(steps, vec![Event::Step(-1), Event::Step(1)]);
+        assert_eq!(se.jog_position(), Some(720));
+    }
+
+    #[test]
+    fn led_flash_is_written_and_cleared() {
+        let transport = MockTransport::new();
+        let mut se = authenticated(&transport);
+
+        se.set_key_led(KeyLed::Cut, true).unwrap();
+        se.add_led_effect(LedEffect::flash(
+            vec![KeyLed::Snap],
+            Duration::from_millis(5),
+        ))
+        .unwrap();
+        let until = Instant::now() + Duration::from_millis(20);
+        while Instant::now() < until {
+            se.step(1).unwrap();
+        }
+
+        assert_eq!(
+            transport.written(),
+            vec![
+                vec![0x2, 0x2, 0x0, 0x0, 0x0, 0x0],
+                vec![0x2, 0x22, 0x0, 0x0, 0x0, 0x0],
+                vec![0x2, 0x2, 0x0, 0x0, 0x0, 0x0],
+            ]
+        );
+        assert!(se.led_effects.is_empty());
+    }
+
+    #[test]
+    fn outputs_are_restored_after_reconnect() {
+        let unplugged = MockTransport::new();
+        unplugged.push_auth_handshake();
+        unplugged.push_input_report(&[0x4, 0x7, 0x0]);
+        unplugged.unplug();
+        let replugged = MockTransport::new();
+        replugged.push_auth_handshake();
+        replugged.push_input_report(&[0x4, 0x0, 0x0]);
+
+        let connector = MockConnector::new();
+        connector.push(unplugged);
+        connector.push(replugged.clone());
+        let mut se = crate::with_connector(connector).unwrap();
+        se.set_config(SpeedEditorConfig::new().reconnect_interval(Duration::from_millis(1)));
+        se.set_key_led(KeyLed::Cam1, true).unwrap();
+        se.set_jog_mode(JogMode::Absolute).unwrap();
+        se.set_jog_led(JogLed::Shtl, true).unwrap();
+
+        let events: Vec<Event> = se.events().take(6).map(|e| e.unwrap()).collect();
+        assert_eq!(events[3], Event::Disconnected);
+        assert_eq!(events[4], Event::Connected);
+        assert_eq!(events[5], Event::KeyUp(Key::In));
+        assert_eq!(
+            replugged.written(),
+            vec![
+                vec![0x3, 0x1, 0x0, 0x0, 0x0, 0x0, 0xff],
+                vec![0x2, 0x0, 0x40, 0x0, 0x0, 0x0],
+                vec![0x4, 0x2],
+            ]
+        );
+    }
+
+    #[test]
+    fn layer_switch_keys_change_layer_and_leds() {
+        let transport = MockTransport::new();
+        // Source down with In, then both released
+        transport.push_input_report(&[0x4, 0x1a, 0x0]);
+        transport.push_input_report(&[0x4, 0x1a, 0x0, 0x7, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0]);
+
+        let mut se = authenticated(&transport);
+        se.layers.add_layer("color", vec![KeyLed::Cam1]);
+        se.layers.momentary(Key::Source, "color");
+        let layers = Arc::new(Mutex::new(vec![]));
+        let seen = layers.clone();
+        se.on_layer_changed(move |name| {
+            seen.lock().unwrap().push(name);
+            Ok(())
+        });
+
+        let events: Vec<Event> = se
+            .events()
+            .take(6)
+            .map(|e| e.unwrap())
+            .filter(|e| !matches!(e, Event::Keys(_)))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                Event::LayerChanged("color".to_string()),
+                Event::KeyDown(Key::In),
+                Event::LayerChanged("base".to_string()),
+                Event::KeyUp(Key::In),
+            ]
+        );
+        assert_eq!(*layers.lock().unwrap(), vec!["color", "base"]);
+        assert_eq!(
+            transport.written(),
+            vec![
+                vec![0x2, 0x0, 0x40, 0x0, 0x0, 0x0],
+                vec![0x2, 0x0, 0x0, 0x0, 0x0, 0x0],
+            ]
+        );
+    }
 }
@@ -0,0 +1,123 @@
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    thread::JoinHandle,
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use super::{
+    controller::Controller, Event, JogLed, JogMode, KeyLed, SpeedEditor, SpeedEditorError,
+    SpeedEditorResult,
+};
+
+// Runs a SpeedEditor on a dedicated thread and exposes its events as a Stream.
+// The stream ends when the device loop stops, e.g. when a callback returns an error.
+pub struct AsyncSpeedEditor {
+    controller: Controller,
+    events: UnboundedReceiver<Event>,
+    handle: Option<JoinHandle<SpeedEditorResult>>,
+}
+
+impl AsyncSpeedEditor {
+    pub fn new(speed_editor: SpeedEditor) -> AsyncSpeedEditor {
+        let (event_tx, events) = unbounded_channel();
+        let (controller, handle) =
+            speed_editor.spawn_with(move |event| event_tx.send(event).is_ok());
+
+        AsyncSpeedEditor {
+            controller,
+            events,
+            handle: Some(handle),
+        }
+    }
+
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+
+    // Blocking handle for use outside of async code
+    pub fn controller(&self) -> Controller {
+        self.controller.clone()
+    }
+
+    pub async fn set_key_led(&self, led: KeyLed, on: bool) -> SpeedEditorResult {
+        self.controller.set_key_led(led, on)
+    }
+
+    pub async fn set_all_key_leds(&self, on: bool) -> SpeedEditorResult {
+        self.controller.set_all_key_leds(on)
+    }
+
+    pub async fn set_leds(&self, leds: Vec<KeyLed>, on: bool) -> SpeedEditorResult {
+        self.controller.set_leds(leds, on)
+    }
+
+    pub async fn set_jog_led(&self, led: JogLed, on: bool) -> SpeedEditorResult {
+        self.controller.set_jog_led(led, on)
+    }
+
+    pub async fn set_all_jog_leds(&self, on: bool) -> SpeedEditorResult {
+        self.controller.set_all_jog_leds(on)
+    }
+
+    pub async fn set_jog_mode(&self, mode: JogMode) -> SpeedEditorResult {
+        self.controller.set_jog_mode(mode)
+    }
+
+    // Stop the device loop and return its result
+    pub async fn join(mut self) -> SpeedEditorResult {
+        let _ = self.controller.stop();
+
+        match self.handle.take() {
+            Some(handle) => tokio::task::spawn_blocking(move || handle.join())
+                .await
+                .map_err(|_| SpeedEditorError::Stopped)?
+                .map_err(|_| SpeedEditorError::Stopped)?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Stream for AsyncSpeedEditor {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncSpeedEditor;
+    use crate::{Event, Key, KeyLed, MockConnector, MockTransport};
+    use futures_core::Stream;
+    use std::{future::poll_fn, pin::Pin, thread, time::Duration};
+
+    #[tokio::test]
+    async fn streams_events_and_applies_commands() {
+        let transport = MockTransport::new();
+        transport.push_auth_handshake();
+        transport.push_input_report(&[0x4, 0x7, 0x0]);
+
+        let connector = MockConnector::new();
+        connector.push(transport.clone());
+        let mut se = AsyncSpeedEditor::new(crate::with_connector(connector).unwrap());
+
+        assert_eq!(se.next_event().await, Some(Event::Connected));
+        assert_eq!(
+            poll_fn(|cx| Pin::new(&mut se).poll_next(cx)).await,
+            Some(Event::KeyDown(Key::In))
+        );
+
+        se.set_key_led(KeyLed::Cut, true).await.unwrap();
+        while !transport
+            .written()
+            .contains(&vec![0x2, 0x2, 0x0, 0x0, 0x0, 0x0])
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        se.join().await.unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+/*
+ * Authenticate module is taken from:
+ * https://github.com/smunaut/blackmagic-misc
+ * Copyright (C) 2021 Sylvain Munaut <tnt@246tNt.com>
+ *
+ * */
+use std::io::Read;
+
+use super::{transport::Transport, SpeedEditorError, SpeedEditorResult};
+
+const AUTH_EVEN_TBL: [u64; 8] = [
+    4242707987619187656,
+    3069963097229903046,
+    2352841328256802570,
+    12646368222702737177,
+    17018789593460232529,
+    12706253227766860309,
+    11978781369061872007,
+    8438608961089703390,
+];
+
+const AUTH_ODD_TBL: [u64; 8] = [
+    4477338132788707294,
+    2622620659002747676,
+    11637077509869926595,
+    7923852755392722584,
+    8224257920127642516,
+    4049197610885016386,
+    18266591397768539273,
+    7035737829027231430,
+];
+
+const MASK: u64 = 12077075256910773232;
+
+fn rol8(v: u64) -> u64 {
+    v.rotate_right(8)
+}
+
+fn rol8n(mut v: u64, n: u64) -> u64 {
+    for _ in 0..n {
+        v = rol8(v);
+    }
+    v
+}
+
+// Response to a keyboard challenge, as expected by the Speed Editor
+pub fn compute_response(challenge: u64) -> u64 {
+    let n = challenge & 7;
+    let mut v = rol8n(challenge, n);
+    let k = if (v & 1) == ((120 >> n) & 1) {
+        AUTH_EVEN_TBL[n as usize]
+    } else {
+        v ^= rol8(v);
+        AUTH_ODD_TBL[n as usize]
+    };
+
+    v ^ (rol8(v) & MASK) ^ k
+}
+
+// Run the challenge / response handshake over feature report 0x06
+pub fn authenticate(device: &dyn Transport) -> SpeedEditorResult {
+    let mut buf = [0; 8];
+    let mut bytes = vec![0; 10];
+
+    device.send_feature_report(&[0x6, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0])?;
+    bytes[0] = 0x6;
+
+    let _ = device.get_feature_report(&mut bytes)?;
+    if bytes[0] != 0x6 || bytes[1] != 0x0 {
+        return Err(SpeedEditorError::AuthGetKbdChallengeError);
+    }
+
+    (&bytes[2..]).read_exact(&mut buf)?;
+    let challenge = u64::from_le_bytes(buf);
+
+    device.send_feature_report(&[0x6, 0x1, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0])?;
+    let _ = device.get_feature_report(&mut bytes)?;
+    if bytes[0] != 0x6 || bytes[1] != 0x2 {
+        return Err(SpeedEditorError::AuthGetKbdResponseError);
+    }
+
+    buf = compute_response(challenge).to_le_bytes();
+
+    bytes[1] = 0x3;
+    bytes[2..].copy_from_slice(&buf);
+
+    device.send_feature_report(bytes.as_slice())?;
+
+    let _ = device.get_feature_report(&mut bytes)?;
+    if bytes[0] != 0x6 || bytes[1] != 0x4 {
+        return Err(SpeedEditorError::AuthGetKbdStatusError);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{authenticate, compute_response};
+    use crate::{MockTransport, SpeedEditorError};
+
+    #[test]
+    fn known_answers() {
+        let vectors: [(u64, u64); 7] = [
+            (0x0000000000000000, 0x3ae1206f97c10bc8),
+            (0x0000000000000001, 0x2b9ab32bebf244c6),
+            (0x0807060504030201, 0x2992b428eaf747c4),
+            (0x123456789abcdef0, 0x88c756430defede8),
+            (0xffffffffffffffff, 0x61a3f6474ff236c6),
+            (0xdeadbeefcafef00d, 0x6a04b6fcff2b4b21),
+            (0x0f0e0d0c0b0a0906, 0xff7cfe1c833f2e88),
+        ];
+
+        for (challenge, response) in vectors {
+            assert_eq!(compute_response(challenge), response, "{:#x}", challenge);
+        }
+    }
+
+    #[test]
+    fn handshake_sends_response() {
+        let transport = MockTransport::new();
+        transport.push_auth_handshake();
+
+        authenticate(&transport).unwrap();
+
+        let mut response = vec![0x6, 0x3];
+        response.extend_from_slice(&0x2992b428eaf747c4u64.to_le_bytes());
+        assert_eq!(transport.sent_feature_reports()[2], response);
+    }
+
+    #[test]
+    fn handshake_rejects_bad_status() {
+        let transport = MockTransport::new();
+        transport.push_feature_report(&[0x6, 0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8]);
+        transport.push_feature_report(&[0x6, 0x2, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0]);
+        transport.push_feature_report(&[0x6, 0x5, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0]);
+
+        assert!(matches!(
+            authenticate(&transport),
+            Err(SpeedEditorError::AuthGetKbdStatusError)
+        ));
+    }
+}
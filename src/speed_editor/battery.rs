@@ -0,0 +1,30 @@
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BatteryStatus {
+    pub level_percent: u8,
+    pub charging: bool,
+}
+
+impl BatteryStatus {
+    // Report 0x07: [report id, charging, level]
+    pub fn from_report(buf: &[u8]) -> Option<BatteryStatus> {
+        match buf {
+            [0x7, charging, level, ..] => Some(BatteryStatus {
+                level_percent: (*level).min(100),
+                charging: *charging != 0,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BatteryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.charging {
+            write!(f, "{}% (charging)", self.level_percent)
+        } else {
+            write!(f, "{}%", self.level_percent)
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, BufRead, Write},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    transport::{Connector, Transport},
+    SpeedEditorError,
+};
+
+// Capture files have one report per line: `<milliseconds> <kind> <hex bytes>`.
+// Empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CaptureKind {
+    // Input report read from the panel
+    In,
+    // Output report written to the panel, e.g. LEDs
+    Out,
+    // Feature report sent to the panel
+    FeatureOut,
+    // Feature report received from the panel
+    FeatureIn,
+}
+
+impl fmt::Display for CaptureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            CaptureKind::In => "in",
+            CaptureKind::Out => "out",
+            CaptureKind::FeatureOut => "feature-out",
+            CaptureKind::FeatureIn => "feature-in",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl FromStr for CaptureKind {
+    type Err = SpeedEditorError;
+
+    fn from_str(s: &str) -> Result<CaptureKind, SpeedEditorError> {
+        match s {
+            "in" => Ok(CaptureKind::In),
+            "out" => Ok(CaptureKind::Out),
+            "feature-out" => Ok(CaptureKind::FeatureOut),
+            "feature-in" => Ok(CaptureKind::FeatureIn),
+            _ => Err(SpeedEditorError::CaptureParseError(format!(
+                "unknown kind `{}`",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CaptureRecord {
+    pub elapsed: Duration,
+    pub kind: CaptureKind,
+    pub data: Vec<u8>,
+}
+
+impl fmt::Display for CaptureRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ", self.elapsed.as_millis(), self.kind)?;
+        for b in self.data.iter() {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CaptureRecord {
+    type Err = SpeedEditorError;
+
+    fn from_str(line: &str) -> Result<CaptureRecord, SpeedEditorError> {
+        let invalid =
+            |what: &str| SpeedEditorError::CaptureParseError(format!("{} in `{}`", what, line));
+
+        let mut fields = line.split_whitespace();
+        let elapsed = fields
+            .next()
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or_else(|| invalid("invalid time"))?;
+        let kind = fields
+            .next()
+            .ok_or_else(|| invalid("missing kind"))?
+            .parse()?;
+        let hex = fields.next().unwrap_or("");
+
+        if !hex.len().is_multiple_of(2) {
+            return Err(invalid("odd number of hex digits"));
+        }
+        let data = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid("invalid hex"))?;
+
+        Ok(CaptureRecord {
+            elapsed: Duration::from_millis(elapsed),
+            kind,
+            data,
+        })
+    }
+}
+
+// Read every record of a capture file
+pub fn read_capture<R: BufRead>(reader: R) -> Result<Vec<CaptureRecord>, SpeedEditorError> {
+    let mut records = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let record = line.parse().map_err(|e| match e {
+            SpeedEditorError::CaptureParseError(msg) => {
+                SpeedEditorError::CaptureParseError(format!("line {}: {}", i + 1, msg))
+            }
+            e => e,
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[derive(Clone)]
+struct CaptureWriter {
+    started_at: Instant,
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl CaptureWriter {
+    fn record(&self, kind: CaptureKind, data: &[u8]) -> io::Result<()> {
+        let record = CaptureRecord {
+            elapsed: self.started_at.elapsed(),
+            kind,
+            data: data.to_vec(),
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{}", record)?;
+        writer.flush()
+    }
+}
+
+// Records every report passing through the transports opened by `inner`
+pub struct CaptureConnector<C> {
+    inner: C,
+    writer: CaptureWriter,
+}
+
+impl<C: Connector> CaptureConnector<C> {
+    pub fn new<W>(inner: C, writer: W) -> CaptureConnector<C>
+    where
+        W: Write + Send + 'static,
+    {
+        CaptureConnector {
+            inner,
+            writer: CaptureWriter {
+                started_at: Instant::now(),
+                writer: Arc::new(Mutex::new(Box::new(writer))),
+            },
+        }
+    }
+}
+
+impl<C: Connector> Connector for CaptureConnector<C> {
+    fn open(&mut self) -> Result<Option<Box<dyn Transport>>, SpeedEditorError> {
+        Ok(self.inner.open()?.map(|inner| {
+            Box::new(CaptureTransport {
+                inner,
+                writer: self.writer.clone(),
+            }) as Box<dyn Transport>
+        }))
+    }
+}
+
+struct CaptureTransport {
+    inner: Box<dyn Transport>,
+    writer: CaptureWriter,
+}
+
+impl Transport for CaptureTransport {
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, SpeedEditorError> {
+        let len = self.inner.read_timeout(buf, timeout)?;
+        if len > 0 {
+            self.writer.record(CaptureKind::In, &buf[..len])?;
+        }
+        Ok(len)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, SpeedEditorError> {
+        self.writer.record(CaptureKind::Out, data)?;
+        self.inner.write(data)
+    }
+
+    fn send_feature_report(&self, data: &[u8]) -> Result<(), SpeedEditorError> {
+        self.writer.record(CaptureKind::FeatureOut, data)?;
+        self.inner.send_feature_report(data)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize, SpeedEditorError> {
+        let len = self.inner.get_feature_report(buf)?;
+        self.writer.record(CaptureKind::FeatureIn, &buf[..len])?;
+        Ok(len)
+    }
+}
+
+// Plays a capture back as a single connection. Input reports are delivered at
+// their recorded times divided by `speed`; f64::INFINITY replays without waiting.
+// A speed that is not above zero (or NaN) replays in real time.
+// Once the capture is exhausted reads fail, as if the panel was unplugged.
+pub struct ReplayConnector {
+    records: Option<Vec<CaptureRecord>>,
+    speed: f64,
+}
+
+impl ReplayConnector {
+    pub fn new(records: Vec<CaptureRecord>) -> ReplayConnector {
+        ReplayConnector {
+            records: Some(records),
+            speed: 1.0,
+        }
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<ReplayConnector, SpeedEditorError> {
+        Ok(ReplayConnector::new(read_capture(reader)?))
+    }
+
+    pub fn speed(mut self, speed: f64) -> ReplayConnector {
+        self.speed = if speed > 0.0 { speed } else { 1.0 };
+        self
+    }
+}
+
+impl Connector for ReplayConnector {
+    fn open(&mut self) -> Result<Option<Box<dyn Transport>>, SpeedEditorError> {
+        Ok(self.records.take().map(|records| {
+            let (input, feature): (Vec<_>, Vec<_>) = records
+                .into_iter()
+                .filter(|r| matches!(r.kind, CaptureKind::In | CaptureKind::FeatureIn))
+                .partition(|r| r.kind == CaptureKind::In);
+
+            Box::new(ReplayTransport {
+                state: Mutex::new(ReplayState {
+                    started_at: None,
+                    input: input.into(),
+                    feature: feature.into(),
+                }),
+                speed: self.speed,
+            }) as Box<dyn Transport>
+        }))
+    }
+}
+
+struct ReplayState {
+    started_at: Option<Instant>,
+    input: VecDeque<CaptureRecord>,
+    feature: VecDeque<CaptureRecord>,
+}
+
+struct ReplayTransport {
+    state: Mutex<ReplayState>,
+    speed: f64,
+}
+
+impl Transport for ReplayTransport {
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, SpeedEditorError> {
+        let mut state = self.state.lock().unwrap();
+        let started_at = *state.started_at.get_or_insert_with(Instant::now);
+
+        // None when the record is due too far ahead to be represented
+        let due = match state.input.front() {
+            Some(record) => Duration::try_from_secs_f64(record.elapsed.as_secs_f64() / self.speed)
+                .ok()
+                .and_then(|delay| started_at.checked_add(delay)),
+            None => {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of capture").into())
+            }
+        };
+
+        let now = Instant::now();
+        let timeout = Duration::from_millis(timeout.max(0) as u64);
+        let due = match due {
+            Some(due) if due <= now + timeout => due,
+            _ => {
+                thread::sleep(timeout);
+                return Ok(0);
+            }
+        };
+        thread::sleep(due.saturating_duration_since(now));
+
+        let record = state.input.pop_front().unwrap();
+        let len = record.data.len().min(buf.len());
+        buf[..len].copy_from_slice(&record.data[..len]);
+        Ok(len)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, SpeedEditorError> {
+        Ok(data.len())
+    }
+
+    fn send_feature_report(&self, _data: &[u8]) -> Result<(), SpeedEditorError> {
+        Ok(())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize, SpeedEditorError> {
+        match self.state.lock().unwrap().feature.pop_front() {
+            Some(record) => {
+                let len = record.data.len().min(buf.len());
+                buf[..len].copy_from_slice(&record.data[..len]);
+                Ok(len)
+            }
+            None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of capture").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        read_capture, CaptureConnector, CaptureKind, CaptureRecord, Connector, ReplayConnector,
+    };
+    use crate::{Event, Key, MockConnector, MockTransport, SpeedEditorError};
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let record: CaptureRecord = "1234 in 0407000800".parse().unwrap();
+        assert_eq!(
+            record,
+            CaptureRecord {
+                elapsed: Duration::from_millis(1234),
+                kind: CaptureKind::In,
+                data: vec![0x4, 0x7, 0x0, 0x8, 0x0],
+            }
+        );
+        assert_eq!(record.to_string(), "1234 in 0407000800");
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let capture = "# session\n0 in 04\n5 sideways 00\n";
+        match read_capture(capture.as_bytes()) {
+            Err(SpeedEditorError::CaptureParseError(msg)) => assert!(msg.starts_with("line 3")),
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn captured_session_replays() {
+        let transport = MockTransport::new();
+        transport.push_auth_handshake();
+        transport.push_input_report(&[0x4, 0x7, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0]);
+        transport.unplug();
+        let connector = MockConnector::new();
+        connector.push(transport);
+
+        let buffer = Buffer::default();
+        let mut se =
+            crate::with_connector(CaptureConnector::new(connector, buffer.clone())).unwrap();
+        let recorded: Vec<Event> = se
+            .events()
+            .map(|e| e.unwrap())
+            .take_while(|e| *e != Event::Disconnected)
+            .collect();
+        drop(se);
+
+        let capture = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let records = read_capture(capture.as_bytes()).unwrap();
+        // 6 handshake reports, 3 restored outputs and 2 key reports
+        assert_eq!(records.len(), 11);
+
+        let replay = ReplayConnector::new(records).speed(f64::INFINITY);
+        let mut se = crate::with_connector(replay).unwrap();
+        let replayed: Vec<Event> = se
+            .events()
+            .map(|e| e.unwrap())
+            .take_while(|e| *e != Event::Disconnected)
+            .collect();
+
+        assert_eq!(replayed, recorded);
+        assert!(replayed.contains(&Event::KeyUp(Key::In)));
+    }
+
+    #[test]
+    fn invalid_replay_speed_replays_in_real_time() {
+        for speed in [0.0, -2.0, f64::NAN] {
+            let records = read_capture("5 in 04070000\n".as_bytes()).unwrap();
+            let mut replay = ReplayConnector::new(records).speed(speed);
+            let transport = replay.open().unwrap().unwrap();
+
+            let mut buf = [0; 8];
+            assert_eq!(transport.read_timeout(&mut buf, 100).unwrap(), 4);
+        }
+    }
+}
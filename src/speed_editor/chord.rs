@@ -0,0 +1,284 @@
+use super::Key;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChordTrigger {
+    // Fires as soon as the last key of the chord goes down
+    Press,
+    // Fires when a key of the chord is released, unless another key was pressed meanwhile
+    Release,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Chord {
+    pub keys: Vec<Key>,
+    pub trigger: ChordTrigger,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum ChordOutput {
+    Key(Key, bool),
+    Chord(Vec<Key>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum HeldState {
+    // KeyDown was reported, KeyUp will be too
+    Reported,
+    // KeyDown is held back until we know whether the key is part of a chord
+    Deferred,
+    // Used by a chord, nothing is reported for this key
+    Consumed,
+}
+
+// Recognises key combinations on top of the raw key transitions.
+//
+// A chord matches when exactly its keys are held. Modifiers must be pressed
+// before the other keys of a chord, so Shtl then Cut matches [Shtl, Cut] but
+// Cut then Shtl does not. Modifiers report nothing while held; released
+// without having been combined with anything, they report a plain key press.
+// The KeyDown / KeyUp of the key completing a chord are suppressed, keys
+// already reported before the chord still get their KeyUp.
+#[derive(Clone, Default, Debug)]
+pub struct ChordRegistry {
+    chords: Vec<Chord>,
+    modifiers: Vec<Key>,
+    held: Vec<(Key, HeldState)>,
+    armed: Option<usize>,
+}
+
+impl ChordRegistry {
+    pub fn new() -> ChordRegistry {
+        ChordRegistry::default()
+    }
+
+    pub fn add(&mut self, keys: &[Key]) {
+        self.add_chord(keys, ChordTrigger::Press);
+    }
+
+    pub fn add_on_release(&mut self, keys: &[Key]) {
+        self.add_chord(keys, ChordTrigger::Release);
+    }
+
+    pub fn add_chord(&mut self, keys: &[Key], trigger: ChordTrigger) {
+        self.chords.push(Chord {
+            keys: keys.to_vec(),
+            trigger,
+        });
+    }
+
+    pub fn add_modifier(&mut self, key: Key) {
+        if !self.modifiers.contains(&key) {
+            self.modifiers.push(key);
+        }
+    }
+
+    pub fn chords(&self) -> &[Chord] {
+        &self.chords
+    }
+
+    pub fn modifiers(&self) -> &[Key] {
+        &self.modifiers
+    }
+
+    // Also forgets a pending release chord and the keys held back for one.
+    // Keys whose KeyDown was reported still get their KeyUp.
+    pub fn clear(&mut self) {
+        self.chords.clear();
+        self.modifiers.clear();
+        self.armed = None;
+        self.held.retain(|(_, state)| *state == HeldState::Reported);
+    }
+
+    pub(crate) fn key_down(&mut self, key: Key) -> Vec<ChordOutput> {
+        let mut out = vec![];
+
+        // Another key cancels a pending release chord
+        if self.armed.take().is_some() {
+            for (k, state) in self.held.iter_mut() {
+                if *state == HeldState::Deferred && !self.modifiers.contains(k) {
+                    *state = HeldState::Reported;
+                    out.push(ChordOutput::Key(*k, true));
+                }
+            }
+        }
+
+        // Anything pressed while a modifier is held means the modifier was used
+        for (_, state) in self.held.iter_mut() {
+            if *state == HeldState::Deferred {
+                *state = HeldState::Consumed;
+            }
+        }
+
+        self.held.push((key, HeldState::Deferred));
+
+        let state = match self.find() {
+            Some(index) => match self.chords[index].trigger {
+                ChordTrigger::Press => {
+                    out.push(ChordOutput::Chord(self.chords[index].keys.clone()));
+                    HeldState::Consumed
+                }
+                ChordTrigger::Release => {
+                    self.armed = Some(index);
+                    HeldState::Deferred
+                }
+            },
+            None if self.modifiers.contains(&key) => HeldState::Deferred,
+            None => {
+                out.push(ChordOutput::Key(key, true));
+                HeldState::Reported
+            }
+        };
+        if let Some(last) = self.held.last_mut() {
+            last.1 = state;
+        }
+
+        out
+    }
+
+    pub(crate) fn key_up(&mut self, key: Key) -> Vec<ChordOutput> {
+        let mut out = vec![];
+
+        if let Some(chord) = self.armed.take().and_then(|index| self.chords.get(index)) {
+            out.push(ChordOutput::Chord(chord.keys.clone()));
+            for (_, state) in self.held.iter_mut() {
+                if *state == HeldState::Deferred {
+                    *state = HeldState::Consumed;
+                }
+            }
+        }
+
+        let position = match self.held.iter().position(|(k, _)| *k == key) {
+            Some(position) => position,
+            None => return out,
+        };
+        match self.held.remove(position).1 {
+            HeldState::Reported => out.push(ChordOutput::Key(key, false)),
+            HeldState::Deferred => {
+                out.push(ChordOutput::Key(key, true));
+                out.push(ChordOutput::Key(key, false));
+            }
+            HeldState::Consumed => {}
+        }
+
+        out
+    }
+
+    // The chord made of exactly the held keys, with modifiers pressed first
+    fn find(&self) -> Option<usize> {
+        self.chords.iter().position(|chord| {
+            if chord.keys.len() != self.held.len()
+                || !self.held.iter().all(|(k, _)| chord.keys.contains(k))
+            {
+                return false;
+            }
+            let last_modifier = self
+                .held
+                .iter()
+                .rposition(|(k, _)| self.modifiers.contains(k));
+            let first_key = self
+                .held
+                .iter()
+                .position(|(k, _)| !self.modifiers.contains(k));
+            match (last_modifier, first_key) {
+                (Some(m), Some(k)) => m < k,
+                _ => true,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChordOutput, ChordRegistry, Key};
+
+    fn key(key: Key, down: bool) -> ChordOutput {
+        ChordOutput::Key(key, down)
+    }
+
+    #[test]
+    fn chord_fires_on_press_and_suppresses_completing_key() {
+        let mut chords = ChordRegistry::new();
+        chords.add(&[Key::Trans, Key::Cut]);
+
+        assert_eq!(chords.key_down(Key::Trans), vec![key(Key::Trans, true)]);
+        assert_eq!(
+            chords.key_down(Key::Cut),
+            vec![ChordOutput::Chord(vec![Key::Trans, Key::Cut])]
+        );
+        assert_eq!(chords.key_up(Key::Cut), vec![]);
+        assert_eq!(chords.key_up(Key::Trans), vec![key(Key::Trans, false)]);
+    }
+
+    #[test]
+    fn modifier_must_be_pressed_first() {
+        let mut chords = ChordRegistry::new();
+        chords.add_modifier(Key::Shtl);
+        chords.add(&[Key::Shtl, Key::Cut]);
+
+        assert_eq!(chords.key_down(Key::Shtl), vec![]);
+        assert_eq!(
+            chords.key_down(Key::Cut),
+            vec![ChordOutput::Chord(vec![Key::Shtl, Key::Cut])]
+        );
+        assert_eq!(chords.key_up(Key::Cut), vec![]);
+        assert_eq!(chords.key_up(Key::Shtl), vec![]);
+
+        assert_eq!(chords.key_down(Key::Cut), vec![key(Key::Cut, true)]);
+        assert_eq!(chords.key_down(Key::Shtl), vec![]);
+        assert_eq!(
+            chords.key_up(Key::Shtl),
+            vec![key(Key::Shtl, true), key(Key::Shtl, false)]
+        );
+        assert_eq!(chords.key_up(Key::Cut), vec![key(Key::Cut, false)]);
+    }
+
+    #[test]
+    fn lone_modifier_is_reported_on_release() {
+        let mut chords = ChordRegistry::new();
+        chords.add_modifier(Key::Shtl);
+
+        assert_eq!(chords.key_down(Key::Shtl), vec![]);
+        assert_eq!(
+            chords.key_up(Key::Shtl),
+            vec![key(Key::Shtl, true), key(Key::Shtl, false)]
+        );
+    }
+
+    #[test]
+    fn release_chord_is_cancelled_by_another_key() {
+        let mut chords = ChordRegistry::new();
+        chords.add_modifier(Key::Shtl);
+        chords.add_on_release(&[Key::Shtl, Key::Cut]);
+
+        chords.key_down(Key::Shtl);
+        assert_eq!(chords.key_down(Key::Cut), vec![]);
+        assert_eq!(
+            chords.key_up(Key::Cut),
+            vec![ChordOutput::Chord(vec![Key::Shtl, Key::Cut])]
+        );
+        assert_eq!(chords.key_up(Key::Shtl), vec![]);
+
+        chords.key_down(Key::Shtl);
+        chords.key_down(Key::Cut);
+        assert_eq!(
+            chords.key_down(Key::In),
+            vec![key(Key::Cut, true), key(Key::In, true)]
+        );
+        assert_eq!(chords.key_up(Key::Cut), vec![key(Key::Cut, false)]);
+    }
+
+    #[test]
+    fn clear_forgets_an_armed_release_chord() {
+        let mut chords = ChordRegistry::new();
+        chords.add(&[Key::In, Key::Out]);
+        chords.add_on_release(&[Key::Trans, Key::Cut]);
+
+        assert_eq!(chords.key_down(Key::Trans), vec![key(Key::Trans, true)]);
+        assert_eq!(chords.key_down(Key::Cut), vec![]);
+        chords.clear();
+        chords.add(&[Key::Cam1, Key::Cam2]);
+
+        assert_eq!(chords.key_up(Key::Cut), vec![]);
+        assert_eq!(chords.key_up(Key::Trans), vec![key(Key::Trans, false)]);
+    }
+}
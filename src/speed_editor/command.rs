@@ -0,0 +1,51 @@
+use std::sync::mpsc::Sender;
+
+use super::{EffectId, JogLed, JogMode, KeyLed, LedEffect, SpeedEditor, SpeedEditorResult};
+
+// Hands the result of a command back to the Controller that sent it. Not part
+// of what the command asks for, so it does not take part in comparisons.
+#[derive(Clone, Debug)]
+pub(crate) struct Reply<T>(pub(crate) Sender<T>);
+
+impl<T> PartialEq for Reply<T> {
+    fn eq(&self, _: &Reply<T>) -> bool {
+        true
+    }
+}
+
+// Requests sent to a SpeedEditor running on another thread
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum Command {
+    KeyLed(KeyLed, bool),
+    AllKeyLeds(bool),
+    Leds(Vec<KeyLed>, bool),
+    JogLed(JogLed, bool),
+    AllJogLeds(bool),
+    JogMode(JogMode),
+    LedEffect(LedEffect, Reply<EffectId>),
+    RemoveLedEffect(EffectId),
+    ClearLedEffects,
+    Stop,
+}
+
+impl SpeedEditor {
+    pub(crate) fn apply(&mut self, command: Command) -> SpeedEditorResult {
+        match command {
+            Command::KeyLed(led, on) => self.set_key_led(led, on),
+            Command::AllKeyLeds(on) => self.set_all_key_leds(on),
+            Command::Leds(leds, on) => self.set_leds(leds, on),
+            Command::JogLed(led, on) => self.set_jog_led(led, on),
+            Command::AllJogLeds(on) => self.set_all_jog_leds(on),
+            Command::JogMode(mode) => self.set_jog_mode(mode),
+            Command::LedEffect(effect, reply) => {
+                let id = self.add_led_effect(effect)?;
+                // The Controller may have stopped waiting
+                let _ = reply.0.send(id);
+                Ok(())
+            }
+            Command::RemoveLedEffect(id) => self.remove_led_effect(id),
+            Command::ClearLedEffects => self.clear_led_effects(),
+            Command::Stop => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SpeedEditorConfig {
+    // How long an authentication stays valid before the handshake is repeated
+    pub auth_interval: Duration,
+    // Failed handshakes retried before the device is reset and reopened
+    pub auth_retries: u32,
+    // Wait before the first retry, doubled for every further one
+    pub auth_backoff: Duration,
+    pub reconnect_interval: Duration,
+    pub read_timeout: Duration,
+}
+
+impl Default for SpeedEditorConfig {
+    fn default() -> SpeedEditorConfig {
+        SpeedEditorConfig {
+            auth_interval: Duration::from_millis(30000),
+            auth_retries: 3,
+            auth_backoff: Duration::from_millis(100),
+            reconnect_interval: Duration::from_millis(100),
+            read_timeout: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl SpeedEditorConfig {
+    pub fn new() -> SpeedEditorConfig {
+        SpeedEditorConfig::default()
+    }
+
+    pub fn auth_interval(mut self, interval: Duration) -> SpeedEditorConfig {
+        self.auth_interval = interval;
+        self
+    }
+
+    pub fn auth_retries(mut self, retries: u32) -> SpeedEditorConfig {
+        self.auth_retries = retries;
+        self
+    }
+
+    pub fn auth_backoff(mut self, backoff: Duration) -> SpeedEditorConfig {
+        self.auth_backoff = backoff;
+        self
+    }
+
+    pub fn reconnect_interval(mut self, interval: Duration) -> SpeedEditorConfig {
+        self.reconnect_interval = interval;
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> SpeedEditorConfig {
+        self.read_timeout = timeout;
+        self
+    }
+
+    // Backoff before retry number `failures` (1-based)
+    pub(crate) fn auth_backoff_for(&self, failures: u32) -> Duration {
+        self.auth_backoff
+            .saturating_mul(1 << failures.saturating_sub(1).min(10))
+    }
+
+    pub(crate) fn read_timeout_ms(&self) -> i32 {
+        self.read_timeout.as_millis().min(i32::MAX as u128) as i32
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use super::{
+    command::{Command, Reply},
+    BatteryStatus, EffectId, Event, JogLed, JogMode, Key, KeyLed, LedEffect, SpeedEditor,
+    SpeedEditorError, SpeedEditorResult,
+};
+
+// Snapshot of the panel as last seen by the device loop
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpeedEditorState {
+    pub connected: bool,
+    pub current_keys: Vec<Key>,
+    pub current_key_leds: Vec<KeyLed>,
+    pub current_jog_mode: JogMode,
+    pub current_jog_leds: Vec<JogLed>,
+    pub battery: Option<BatteryStatus>,
+}
+
+// Handle to a SpeedEditor running on its own thread
+#[derive(Clone)]
+pub struct Controller {
+    commands: Sender<Command>,
+    state: Arc<Mutex<SpeedEditorState>>,
+}
+
+impl Controller {
+    pub fn set_key_led(&self, led: KeyLed, on: bool) -> SpeedEditorResult {
+        self.send(Command::KeyLed(led, on))
+    }
+
+    pub fn set_all_key_leds(&self, on: bool) -> SpeedEditorResult {
+        self.send(Command::AllKeyLeds(on))
+    }
+
+    pub fn set_leds(&self, leds: Vec<KeyLed>, on: bool) -> SpeedEditorResult {
+        self.send(Command::Leds(leds, on))
+    }
+
+    pub fn set_jog_led(&self, led: JogLed, on: bool) -> SpeedEditorResult {
+        self.send(Command::JogLed(led, on))
+    }
+
+    pub fn set_all_jog_leds(&self, on: bool) -> SpeedEditorResult {
+        self.send(Command::AllJogLeds(on))
+    }
+
+    pub fn set_jog_mode(&self, mode: JogMode) -> SpeedEditorResult {
+        self.send(Command::JogMode(mode))
+    }
+
+    // Waits for the device loop to add the effect, so it must not be called
+    // from the callbacks of that loop
+    pub fn add_led_effect(&self, effect: LedEffect) -> Result<EffectId, SpeedEditorError> {
+        let (reply, id) = channel();
+        self.send(Command::LedEffect(effect, Reply(reply)))?;
+        id.recv().map_err(|_| SpeedEditorError::Stopped)
+    }
+
+    pub fn remove_led_effect(&self, id: EffectId) -> SpeedEditorResult {
+        self.send(Command::RemoveLedEffect(id))
+    }
+
+    pub fn clear_led_effects(&self) -> SpeedEditorResult {
+        self.send(Command::ClearLedEffects)
+    }
+
+    pub fn state(&self) -> SpeedEditorState {
+        self.state.lock().unwrap().clone()
+    }
+
+    // Ask the device loop to return. Commands sent before are still applied.
+    pub fn stop(&self) -> SpeedEditorResult {
+        self.send(Command::Stop)
+    }
+
+    pub(crate) fn send(&self, command: Command) -> SpeedEditorResult {
+        self.commands
+            .send(command)
+            .map_err(|_| SpeedEditorError::Stopped)
+    }
+}
+
+impl SpeedEditor {
+    const COMMAND_POLL_INTERVAL: u64 = 20;
+
+    // Move the device loop to a new thread. Callbacks keep running on that thread.
+    pub fn spawn(self) -> (Controller, JoinHandle<SpeedEditorResult>) {
+        self.spawn_with(|_| true)
+    }
+
+    // Like spawn(), handing every event to `on_event` until it returns false
+    pub(crate) fn spawn_with<F>(
+        mut self,
+        on_event: F,
+    ) -> (Controller, JoinHandle<SpeedEditorResult>)
+    where
+        F: FnMut(Event) -> bool + Send + 'static,
+    {
+        let (commands, command_rx) = channel();
+        let state = Arc::new(Mutex::new(self.state()));
+
+        let controller = Controller {
+            commands,
+            state: state.clone(),
+        };
+        let handle = thread::spawn(move || self.run_with_commands(command_rx, state, on_event));
+
+        (controller, handle)
+    }
+
+    // The loop of the bridges: run the panel on its own thread with `on_event`
+    // and bridge it by calling `poll` until `token` is stopped or the device
+    // loop ends. `poll` returns false when it had nothing to do, to sleep
+    // `interval` before the next call. `on_event` runs on the device thread;
+    // a send failing there must not stop the panel, as the peer may only not
+    // be listening yet.
+    #[cfg(any(
+        feature = "osc",
+        feature = "midi",
+        feature = "websocket",
+        feature = "mqtt",
+        test
+    ))]
+    pub(crate) fn run_bridge<F, P>(
+        self,
+        token: &super::StopToken,
+        interval: Duration,
+        on_event: F,
+        mut poll: P,
+    ) -> SpeedEditorResult
+    where
+        F: FnMut(Event) -> bool + Send + 'static,
+        P: FnMut(&Controller) -> Result<bool, SpeedEditorError>,
+    {
+        let (controller, handle) = self.spawn_with(on_event);
+
+        let mut result = Ok(());
+        while !token.is_stopped() && !handle.is_finished() {
+            match poll(&controller) {
+                Ok(true) => {}
+                Ok(false) => thread::sleep(interval),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let _ = controller.stop();
+        // A panicked device thread is reported like DeviceManager::stop() does
+        let joined = handle.join().unwrap_or(Err(SpeedEditorError::Stopped));
+        result.and(joined)
+    }
+
+    pub fn state(&self) -> SpeedEditorState {
+        SpeedEditorState {
+            connected: self.device.is_some(),
+            current_keys: self.current_keys.clone(),
+            current_key_leds: self.current_key_leds.clone(),
+            current_jog_mode: self.current_jog_mode,
+            current_jog_leds: self.current_jog_leds.clone(),
+            battery: self.battery,
+        }
+    }
+
+    fn run_with_commands<F>(
+        &mut self,
+        commands: Receiver<Command>,
+        state: Arc<Mutex<SpeedEditorState>>,
+        mut on_event: F,
+    ) -> SpeedEditorResult
+    where
+        F: FnMut(Event) -> bool,
+    {
+        loop {
+            loop {
+                match commands.try_recv() {
+                    Ok(Command::Stop) | Err(TryRecvError::Disconnected) => return Ok(()),
+                    // The state is kept even if the write fails, the read below
+                    // notices an unplugged device.
+                    Ok(command) => {
+                        let _ = self.apply(command);
+                    }
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+
+            let timeout = Duration::from_millis(Self::COMMAND_POLL_INTERVAL);
+            let event = self.poll_event(timeout)?;
+            *state.lock().unwrap() = self.state();
+
+            if let Some(event) = event {
+                if !on_event(event) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        speed_editor::{
+            command::Command,
+            mock::fixtures::{connecting, wait_until, CAM1_LIT},
+        },
+        Key, KeyLed, LedEffect, MockTransport, SpeedEditorError, StopToken,
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    #[test]
+    fn controller_sets_leds_and_stops() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        transport.push_input_report(&[0x4, 0x7, 0x0]);
+        let (controller, handle) = se.spawn();
+
+        while controller.state().current_keys != vec![Key::In] {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(controller.state().connected);
+
+        let other = controller.clone();
+        thread::spawn(move || other.set_key_led(KeyLed::Cam1, true).unwrap())
+            .join()
+            .unwrap();
+        controller.stop().unwrap();
+        handle.join().unwrap().unwrap();
+
+        // The outputs restored after authentication, then Cam1
+        assert_eq!(
+            transport.written(),
+            vec![
+                vec![0x3, 0x0, 0x0, 0x0, 0x0, 0x0, 0xff],
+                vec![0x2, 0x0, 0x0, 0x0, 0x0, 0x0],
+                vec![0x4, 0x0],
+                vec![0x2, 0x0, 0x40, 0x0, 0x0, 0x0],
+            ]
+        );
+        assert!(controller.set_key_led(KeyLed::Cam1, false).is_err());
+    }
+
+    #[test]
+    fn led_effects_are_removed_by_id() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        let (controller, handle) = se.spawn();
+
+        let blink = LedEffect::blink(vec![KeyLed::Cam1], Duration::from_secs(10));
+        let first = controller.add_led_effect(blink.clone()).unwrap();
+        let second = controller.add_led_effect(blink).unwrap();
+        assert_ne!(first, second);
+        assert!(transport.written().contains(&CAM1_LIT.to_vec()));
+
+        controller.remove_led_effect(first).unwrap();
+        controller.remove_led_effect(second).unwrap();
+        controller.stop().unwrap();
+        handle.join().unwrap().unwrap();
+        assert_eq!(
+            transport.written().last(),
+            Some(&vec![0x2, 0x0, 0x0, 0x0, 0x0, 0x0])
+        );
+        assert!(controller
+            .add_led_effect(LedEffect::cam_chase(Duration::from_millis(80)))
+            .is_err());
+    }
+
+    #[test]
+    fn bridge_loop_polls_until_stopped() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        transport.push_input_report(&[0x4, 0xf, 0x0]);
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let seen = events.clone();
+        let on_event = move |event| {
+            seen.lock().unwrap().push(event);
+            true
+        };
+        let token = StopToken::new();
+        let stop = token.clone();
+        let mut sent = false;
+        let handle = thread::spawn(move || {
+            se.run_bridge(&stop, Duration::from_millis(1), on_event, |controller| {
+                if !sent {
+                    controller.send(Command::KeyLed(KeyLed::Cam1, true))?;
+                    sent = true;
+                }
+                Ok(false)
+            })
+        });
+
+        let lit = wait_until(|| transport.written().contains(&CAM1_LIT.to_vec()));
+        token.stop();
+        handle.join().unwrap().unwrap();
+        assert!(lit);
+        assert!(!events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn bridge_loop_returns_poll_errors() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+
+        let result = se.run_bridge(
+            &StopToken::new(),
+            Duration::from_millis(1),
+            |_| true,
+            |_| Err(SpeedEditorError::Stopped),
+        );
+        assert!(matches!(result, Err(SpeedEditorError::Stopped)));
+    }
+}
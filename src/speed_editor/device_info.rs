@@ -0,0 +1,18 @@
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DeviceInfo {
+    pub path: String,
+    pub serial_number: Option<String>,
+    pub interface_number: i32,
+    pub product: Option<String>,
+    pub release_number: u16,
+}
+
+impl DeviceInfo {
+    // Stable name for the unit: its serial number, or the path if it has none
+    pub fn id(&self) -> String {
+        match &self.serial_number {
+            Some(serial) if !serial.is_empty() => serial.clone(),
+            _ => self.path.clone(),
+        }
+    }
+}
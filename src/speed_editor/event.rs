@@ -0,0 +1,27 @@
+use super::{BatteryStatus, Gesture, JogMode, JogMotion, Key};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event {
+    Connected,
+    Disconnected,
+    KeyDown(Key),
+    KeyUp(Key),
+    // A key code missing from Key, with its down / up state
+    UnknownKey(u8, bool),
+    // All keys held after a key down / up
+    Keys(Vec<Key>),
+    // Keys of the registered chord, see ChordRegistry
+    Chord(Vec<Key>),
+    // Name of the layer now active, see LayerStack
+    LayerChanged(String),
+    Gesture(Gesture),
+    Jog(JogMode, i32),
+    // Only reported with jog processing enabled
+    JogMotion(JogMotion),
+    // Playback speed in shuttle mode, e.g. -32.0..32.0
+    ShuttleSpeed(f32),
+    // +1 or -1 per detent, only reported with jog tracking enabled
+    Step(i32),
+    Battery(BatteryStatus),
+    Unknown(Vec<u8>),
+}
@@ -0,0 +1,301 @@
+use std::time::{Duration, Instant};
+
+use super::Key;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Gesture {
+    // Pressed and released once, reported after the double tap window
+    Tap(Key),
+    DoubleTap(Key),
+    // Released after being held for at least the long press threshold
+    LongPress(Key, Duration),
+    // Auto-repeat while held, with the repeat count starting at 1. A press that
+    // repeated ends as LongPress or not at all, never as Tap or DoubleTap.
+    Held(Key, u32),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GestureConfig {
+    pub double_tap_window: Duration,
+    pub long_press: Duration,
+    // First Held after `repeat_delay`, then one every `repeat_interval`. None disables Held.
+    pub repeat: Option<(Duration, Duration)>,
+}
+
+impl Default for GestureConfig {
+    fn default() -> GestureConfig {
+        GestureConfig {
+            double_tap_window: Duration::from_millis(250),
+            long_press: Duration::from_millis(600),
+            repeat: Some((Duration::from_millis(500), Duration::from_millis(100))),
+        }
+    }
+}
+
+impl GestureConfig {
+    const MIN_REPEAT_INTERVAL: Duration = Duration::from_millis(1);
+
+    pub fn new() -> GestureConfig {
+        GestureConfig::default()
+    }
+
+    // Zero reports every tap immediately and disables DoubleTap
+    pub fn double_tap_window(mut self, window: Duration) -> GestureConfig {
+        self.double_tap_window = window;
+        self
+    }
+
+    pub fn long_press(mut self, threshold: Duration) -> GestureConfig {
+        self.long_press = threshold;
+        self
+    }
+
+    // The interval is at least 1 ms
+    pub fn repeat(mut self, delay: Duration, interval: Duration) -> GestureConfig {
+        self.repeat = Some((delay, interval.max(Self::MIN_REPEAT_INTERVAL)));
+        self
+    }
+
+    pub fn no_repeat(mut self) -> GestureConfig {
+        self.repeat = None;
+        self
+    }
+
+    // `repeat` with the interval clamped, as the field may be set directly
+    fn repeat_timing(&self) -> Option<(Duration, Duration)> {
+        self.repeat
+            .map(|(delay, interval)| (delay, interval.max(Self::MIN_REPEAT_INTERVAL)))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum KeyState {
+    Pressed {
+        at: Instant,
+        second: bool,
+        repeats: u32,
+    },
+    Released {
+        at: Instant,
+    },
+}
+
+// Turns key down / up transitions into gestures. Call tick() regularly
+// (before next_deadline()) so taps and repeats are reported on time.
+pub struct GestureDetector {
+    pub config: GestureConfig,
+    keys: Vec<(Key, KeyState)>,
+}
+
+impl GestureDetector {
+    pub fn new(config: GestureConfig) -> GestureDetector {
+        GestureDetector {
+            config,
+            keys: vec![],
+        }
+    }
+
+    pub fn key_down(&mut self, key: Key, now: Instant) -> Vec<Gesture> {
+        let second = matches!(self.state(key), Some(KeyState::Released { at })
+            if now.duration_since(at) < self.config.double_tap_window);
+
+        self.set_state(
+            key,
+            KeyState::Pressed {
+                at: now,
+                second,
+                repeats: 0,
+            },
+        );
+        vec![]
+    }
+
+    pub fn key_up(&mut self, key: Key, now: Instant) -> Vec<Gesture> {
+        let mut gestures = self.tick(now);
+
+        if let Some(KeyState::Pressed {
+            at,
+            second,
+            repeats,
+        }) = self.state(key)
+        {
+            let held = now.duration_since(at);
+            if held >= self.config.long_press {
+                gestures.push(Gesture::LongPress(key, held));
+                self.remove(key);
+            } else if repeats > 0 {
+                self.remove(key);
+            } else if second {
+                gestures.push(Gesture::DoubleTap(key));
+                self.remove(key);
+            } else if self.config.double_tap_window.is_zero() {
+                gestures.push(Gesture::Tap(key));
+                self.remove(key);
+            } else {
+                self.set_state(key, KeyState::Released { at: now });
+            }
+        }
+        gestures
+    }
+
+    // Report taps whose double tap window ran out and due repeats
+    pub fn tick(&mut self, now: Instant) -> Vec<Gesture> {
+        let mut gestures = vec![];
+        let window = self.config.double_tap_window;
+        let repeat = self.config.repeat_timing();
+
+        self.keys.retain_mut(|(key, state)| match state {
+            KeyState::Released { at } if now.duration_since(*at) >= window => {
+                gestures.push(Gesture::Tap(*key));
+                false
+            }
+            KeyState::Pressed { at, repeats, .. } => {
+                if let Some((delay, interval)) = repeat {
+                    while now.duration_since(*at) >= delay + interval * *repeats {
+                        *repeats += 1;
+                        gestures.push(Gesture::Held(*key, *repeats));
+                    }
+                }
+                true
+            }
+            _ => true,
+        });
+        gestures
+    }
+
+    // When tick() has something to report next, if anything
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.keys
+            .iter()
+            .filter_map(|(_, state)| match *state {
+                KeyState::Released { at } => Some(at + self.config.double_tap_window),
+                KeyState::Pressed { at, repeats, .. } => self
+                    .config
+                    .repeat_timing()
+                    .map(|(delay, interval)| at + delay + interval * repeats),
+            })
+            .min()
+    }
+
+    fn state(&self, key: Key) -> Option<KeyState> {
+        self.keys.iter().find(|(k, _)| *k == key).map(|(_, s)| *s)
+    }
+
+    fn set_state(&mut self, key: Key, state: KeyState) {
+        match self.keys.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = state,
+            None => self.keys.push((key, state)),
+        }
+    }
+
+    fn remove(&mut self, key: Key) {
+        self.keys.retain(|(k, _)| *k != key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gesture, GestureConfig, GestureDetector};
+    use crate::Key;
+    use std::time::{Duration, Instant};
+
+    fn ms(v: u64) -> Duration {
+        Duration::from_millis(v)
+    }
+
+    #[test]
+    fn tap_is_reported_after_double_tap_window() {
+        let mut g = GestureDetector::new(GestureConfig::new());
+        let t = Instant::now();
+
+        g.key_down(Key::Esc, t);
+        assert_eq!(g.key_up(Key::Esc, t + ms(50)), vec![]);
+        assert_eq!(g.next_deadline(), Some(t + ms(300)));
+        assert_eq!(g.tick(t + ms(200)), vec![]);
+        assert_eq!(g.tick(t + ms(300)), vec![Gesture::Tap(Key::Esc)]);
+        assert_eq!(g.next_deadline(), None);
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut g = GestureDetector::new(GestureConfig::new());
+        let t = Instant::now();
+
+        g.key_down(Key::Esc, t);
+        g.key_up(Key::Esc, t + ms(50));
+        g.key_down(Key::Esc, t + ms(150));
+        assert_eq!(
+            g.key_up(Key::Esc, t + ms(200)),
+            vec![Gesture::DoubleTap(Key::Esc)]
+        );
+        assert_eq!(g.tick(t + ms(1000)), vec![]);
+    }
+
+    #[test]
+    fn zero_repeat_interval_is_clamped() {
+        let mut config = GestureConfig::new().repeat(ms(100), Duration::ZERO);
+        assert_eq!(config.repeat, Some((ms(100), ms(1))));
+
+        config.repeat = Some((ms(100), Duration::ZERO));
+        let mut g = GestureDetector::new(config);
+        let t = Instant::now();
+
+        g.key_down(Key::Esc, t);
+        assert_eq!(g.tick(t + ms(102)).len(), 3);
+        assert_eq!(g.next_deadline(), Some(t + ms(103)));
+    }
+
+    #[test]
+    fn long_press_with_repeats() {
+        let config = GestureConfig::new().repeat(ms(500), ms(100));
+        let mut g = GestureDetector::new(config);
+        let t = Instant::now();
+
+        g.key_down(Key::StopPlay, t);
+        assert_eq!(g.tick(t + ms(499)), vec![]);
+        assert_eq!(g.tick(t + ms(500)), vec![Gesture::Held(Key::StopPlay, 1)]);
+        assert_eq!(
+            g.tick(t + ms(720)),
+            vec![
+                Gesture::Held(Key::StopPlay, 2),
+                Gesture::Held(Key::StopPlay, 3)
+            ]
+        );
+        assert_eq!(
+            g.key_up(Key::StopPlay, t + ms(750)),
+            vec![Gesture::LongPress(Key::StopPlay, ms(750))]
+        );
+    }
+
+    #[test]
+    fn repeated_press_is_not_a_tap() {
+        let mut g = GestureDetector::new(GestureConfig::new());
+        let t = Instant::now();
+
+        // Past the repeat delay (500 ms), short of long_press (600 ms)
+        g.key_down(Key::Esc, t);
+        assert_eq!(
+            g.key_up(Key::Esc, t + ms(550)),
+            vec![Gesture::Held(Key::Esc, 1)]
+        );
+        assert_eq!(g.next_deadline(), None);
+        assert_eq!(g.tick(t + ms(1000)), vec![]);
+
+        // Nor the first of a double tap
+        g.key_down(Key::Esc, t + ms(600));
+        g.key_up(Key::Esc, t + ms(650));
+        assert_eq!(g.tick(t + ms(900)), vec![Gesture::Tap(Key::Esc)]);
+    }
+
+    #[test]
+    fn zero_window_taps_immediately() {
+        let config = GestureConfig::new()
+            .double_tap_window(Duration::ZERO)
+            .no_repeat();
+        let mut g = GestureDetector::new(config);
+        let t = Instant::now();
+
+        g.key_down(Key::Cut, t);
+        assert_eq!(g.key_up(Key::Cut, t + ms(10)), vec![Gesture::Tap(Key::Cut)]);
+    }
+}
@@ -1,17 +1,34 @@
-use super::{Key, SpeedEditorResult};
+use super::{BatteryStatus, Gesture, JogMode, JogMotion, Key, SpeedEditorResult};
+
+pub type ConnectedCallback = Box<dyn FnMut() -> SpeedEditorResult + Sync + Send>;
+pub type DisconnectedCallback = Box<dyn FnMut() -> SpeedEditorResult + Sync + Send>;
+pub type KeysCallback = Box<dyn FnMut(Vec<Key>) -> SpeedEditorResult + Sync + Send>;
+pub type KeyCallback = Box<dyn FnMut(Key, bool) -> SpeedEditorResult + Sync + Send>;
+pub type KeyDownCallback = Box<dyn FnMut(Key) -> SpeedEditorResult + Sync + Send>;
+pub type KeyUpCallback = Box<dyn FnMut(Key) -> SpeedEditorResult + Sync + Send>;
+pub type UnknownKeyCallback = Box<dyn FnMut(u8, bool) -> SpeedEditorResult + Sync + Send>;
+pub type ChordCallback = Box<dyn FnMut(Vec<Key>) -> SpeedEditorResult + Sync + Send>;
+pub type LayerChangedCallback = Box<dyn FnMut(String) -> SpeedEditorResult + Sync + Send>;
+pub type GestureCallback = Box<dyn FnMut(Gesture) -> SpeedEditorResult + Sync + Send>;
+pub type JogCallback = Box<dyn FnMut(JogMode, i32) -> SpeedEditorResult + Sync + Send>;
+pub type JogMotionCallback = Box<dyn FnMut(JogMotion) -> SpeedEditorResult + Sync + Send>;
+pub type ShuttleSpeedCallback = Box<dyn FnMut(f32) -> SpeedEditorResult + Sync + Send>;
+pub type StepCallback = Box<dyn FnMut(i32) -> SpeedEditorResult + Sync + Send>;
+pub type BatteryCallback = Box<dyn FnMut(BatteryStatus) -> SpeedEditorResult + Sync + Send>;
+pub type UnknownCallback = Box<dyn FnMut(&[u8]) -> SpeedEditorResult + Sync + Send>;
 
 pub trait Handler {
     fn new() -> Self;
 }
 
 pub struct ConnectedHandler {
-    pub callbacks: Vec<Box<dyn FnMut() -> SpeedEditorResult + Sync + Send>>,
+    pub callbacks: Vec<ConnectedCallback>,
 }
 
 impl ConnectedHandler {
     pub fn call(&mut self) -> SpeedEditorResult {
         for callback in self.callbacks.iter_mut() {
-            (&mut *callback)()?;
+            callback()?;
         }
         Ok(())
     }
@@ -24,13 +41,13 @@ impl Handler for ConnectedHandler {
 }
 
 pub struct DisconnectedHandler {
-    pub callbacks: Vec<Box<dyn FnMut() -> SpeedEditorResult + Sync + Send>>,
+    pub callbacks: Vec<DisconnectedCallback>,
 }
 
 impl DisconnectedHandler {
     pub fn call(&mut self) -> SpeedEditorResult {
         for callback in self.callbacks.iter_mut() {
-            (&mut *callback)()?;
+            callback()?;
         }
         Ok(())
     }
@@ -43,13 +60,13 @@ impl Handler for DisconnectedHandler {
 }
 
 pub struct KeysHandler {
-    pub callbacks: Vec<Box<dyn FnMut(Vec<Key>) -> SpeedEditorResult + Sync + Send>>,
+    pub callbacks: Vec<KeysCallback>,
 }
 
 impl KeysHandler {
-    pub fn call(&mut self, keys: &Vec<Key>) -> SpeedEditorResult {
+    pub fn call(&mut self, keys: &[Key]) -> SpeedEditorResult {
         for callback in self.callbacks.iter_mut() {
-            (&mut *callback)(keys.clone())?;
+            callback(keys.to_vec())?;
         }
         Ok(())
     }
@@ -62,13 +79,13 @@ impl Handler for KeysHandler {
 }
 
 pub struct KeyHandler {
-    pub callbacks: Vec<Box<dyn FnMut(Key, bool) -> SpeedEditorResult + Sync + Send>>,
+    pub callbacks: Vec<KeyCallback>,
 }
 
 impl KeyHandler {
     pub fn call(&mut self, key: Key, down: bool) -> SpeedEditorResult {
         for callback in self.callbacks.iter_mut() {
-            (&mut *callback)(key, down)?;
+            callback(key, down)?;
         }
         Ok(())
     }
@@ -81,13 +98,13 @@ impl Handler for KeyHandler {
 }
 
 pub struct KeyDownHandler {
-    pub callbacks: Vec<Box<dyn FnMut(Key) -> SpeedEditorResult + Sync + Send>>,
+    pub callbacks: Vec<KeyDownCallback>,
 }
 
 impl KeyDownHandler {
     pub fn call(&mut self, key: Key) -> SpeedEditorResult {
         for callback in self.callbacks.iter_mut() {
-            (&mut *callback)(key)?;
+            callback(key)?;
         }
         Ok(())
     }
@@ -100,13 +117,13 @@ impl Handler for KeyDownHandler {
 }
 
 pub struct KeyUpHandler {
-    pub callbacks: Vec<Box<dyn FnMut(Key) -> SpeedEditorResult + Sync + Send>>,
+    pub callbacks: Vec<KeyUpCallback>,
 }
 
 impl KeyUpHandler {
     pub fn call(&mut self, key: Key) -> SpeedEditorResult {
         for callback in self.callbacks.iter_mut() {
-            (&mut *callback)(key)?;
+            callback(key)?;
         }
         Ok(())
     }
@@ -118,14 +135,90 @@ impl Handler for KeyUpHandler {
     }
 }
 
+pub struct UnknownKeyHandler {
+    pub callbacks: Vec<UnknownKeyCallback>,
+}
+
+impl UnknownKeyHandler {
+    pub fn call(&mut self, code: u8, down: bool) -> SpeedEditorResult {
+        for callback in self.callbacks.iter_mut() {
+            callback(code, down)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for UnknownKeyHandler {
+    fn new() -> UnknownKeyHandler {
+        UnknownKeyHandler { callbacks: vec![] }
+    }
+}
+
+pub struct ChordHandler {
+    pub callbacks: Vec<ChordCallback>,
+}
+
+impl ChordHandler {
+    pub fn call(&mut self, keys: &[Key]) -> SpeedEditorResult {
+        for callback in self.callbacks.iter_mut() {
+            callback(keys.to_vec())?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for ChordHandler {
+    fn new() -> ChordHandler {
+        ChordHandler { callbacks: vec![] }
+    }
+}
+
+pub struct LayerChangedHandler {
+    pub callbacks: Vec<LayerChangedCallback>,
+}
+
+impl LayerChangedHandler {
+    pub fn call(&mut self, name: &str) -> SpeedEditorResult {
+        for callback in self.callbacks.iter_mut() {
+            callback(name.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for LayerChangedHandler {
+    fn new() -> LayerChangedHandler {
+        LayerChangedHandler { callbacks: vec![] }
+    }
+}
+
+pub struct GestureHandler {
+    pub callbacks: Vec<GestureCallback>,
+}
+
+impl GestureHandler {
+    pub fn call(&mut self, gesture: Gesture) -> SpeedEditorResult {
+        for callback in self.callbacks.iter_mut() {
+            callback(gesture)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for GestureHandler {
+    fn new() -> GestureHandler {
+        GestureHandler { callbacks: vec![] }
+    }
+}
+
 pub struct JogHandler {
-    pub callbacks: Vec<Box<dyn FnMut(u8, i32) -> SpeedEditorResult + Sync + Send>>,
+    pub callbacks: Vec<JogCallback>,
 }
 
 impl JogHandler {
-    pub fn call(&mut self, mode: u8, value: i32) -> SpeedEditorResult {
+    pub fn call(&mut self, mode: JogMode, value: i32) -> SpeedEditorResult {
         for callback in self.callbacks.iter_mut() {
-            (&mut *callback)(mode, value)?;
+            callback(mode, value)?;
         }
         Ok(())
     }
@@ -137,14 +230,90 @@ impl Handler for JogHandler {
     }
 }
 
+pub struct JogMotionHandler {
+    pub callbacks: Vec<JogMotionCallback>,
+}
+
+impl JogMotionHandler {
+    pub fn call(&mut self, motion: JogMotion) -> SpeedEditorResult {
+        for callback in self.callbacks.iter_mut() {
+            callback(motion)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for JogMotionHandler {
+    fn new() -> JogMotionHandler {
+        JogMotionHandler { callbacks: vec![] }
+    }
+}
+
+pub struct ShuttleSpeedHandler {
+    pub callbacks: Vec<ShuttleSpeedCallback>,
+}
+
+impl ShuttleSpeedHandler {
+    pub fn call(&mut self, speed: f32) -> SpeedEditorResult {
+        for callback in self.callbacks.iter_mut() {
+            callback(speed)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for ShuttleSpeedHandler {
+    fn new() -> ShuttleSpeedHandler {
+        ShuttleSpeedHandler { callbacks: vec![] }
+    }
+}
+
+pub struct StepHandler {
+    pub callbacks: Vec<StepCallback>,
+}
+
+impl StepHandler {
+    pub fn call(&mut self, direction: i32) -> SpeedEditorResult {
+        for callback in self.callbacks.iter_mut() {
+            callback(direction)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for StepHandler {
+    fn new() -> StepHandler {
+        StepHandler { callbacks: vec![] }
+    }
+}
+
+pub struct BatteryHandler {
+    pub callbacks: Vec<BatteryCallback>,
+}
+
+impl BatteryHandler {
+    pub fn call(&mut self, status: BatteryStatus) -> SpeedEditorResult {
+        for callback in self.callbacks.iter_mut() {
+            callback(status)?;
+        }
+        Ok(())
+    }
+}
+
+impl Handler for BatteryHandler {
+    fn new() -> BatteryHandler {
+        BatteryHandler { callbacks: vec![] }
+    }
+}
+
 pub struct UnknownHandler {
-    pub callbacks: Vec<Box<dyn FnMut(&[u8]) -> SpeedEditorResult + Sync + Send>>,
+    pub callbacks: Vec<UnknownCallback>,
 }
 
 impl UnknownHandler {
     pub fn call(&mut self, data: &[u8]) -> SpeedEditorResult {
         for callback in self.callbacks.iter_mut() {
-            (&mut *callback)(data)?;
+            callback(data)?;
         }
         Ok(())
     }
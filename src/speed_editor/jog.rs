@@ -0,0 +1,290 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use super::JogMode;
+
+// Raw units per step in the relative modes
+pub const RELATIVE_STEP: f64 = 360.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AccelCurve {
+    // Movement is passed through unchanged
+    Flat,
+    // Gain of 1 + factor * velocity, flat unless the factor is at least 0
+    Linear { factor: f64 },
+    // Gain of (velocity / threshold) ^ exponent above the threshold, 1 below
+    Power { threshold: f64, exponent: f64 },
+}
+
+impl AccelCurve {
+    fn is_valid(&self) -> bool {
+        match *self {
+            AccelCurve::Linear { factor } => factor >= 0.0,
+            _ => true,
+        }
+    }
+
+    // Multiplier for a velocity in steps per second
+    pub fn gain(&self, velocity: f64) -> f64 {
+        let velocity = velocity.abs();
+        match *self {
+            AccelCurve::Flat => 1.0,
+            AccelCurve::Linear { factor } if factor >= 0.0 => 1.0 + factor * velocity,
+            AccelCurve::Linear { .. } => 1.0,
+            AccelCurve::Power {
+                threshold,
+                exponent,
+            } => {
+                if velocity <= threshold || threshold <= 0.0 {
+                    1.0
+                } else {
+                    (velocity / threshold).powf(exponent)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct JogConfig {
+    // Movement averaged to compute the velocity
+    pub velocity_window: Duration,
+    // Applied to the relative modes only
+    pub curve: AccelCurve,
+    // Playback speeds from the centre to the end of the shuttle travel, mirrored for reverse
+    pub shuttle_speeds: Vec<f32>,
+    // Absolute position at the end of the shuttle travel
+    pub shuttle_range: i32,
+    // Positions within this distance of the centre stop playback
+    pub shuttle_deadzone: i32,
+}
+
+impl Default for JogConfig {
+    fn default() -> JogConfig {
+        JogConfig {
+            velocity_window: Duration::from_millis(100),
+            curve: AccelCurve::Flat,
+            shuttle_speeds: vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0],
+            shuttle_range: 4096,
+            shuttle_deadzone: 256,
+        }
+    }
+}
+
+impl JogConfig {
+    pub fn new() -> JogConfig {
+        JogConfig::default()
+    }
+
+    pub fn velocity_window(mut self, window: Duration) -> JogConfig {
+        self.velocity_window = window;
+        self
+    }
+
+    // A Linear curve with a negative or NaN factor is rejected, keeping the curve as it was
+    pub fn curve(mut self, curve: AccelCurve) -> JogConfig {
+        if curve.is_valid() {
+            self.curve = curve;
+        }
+        self
+    }
+
+    pub fn shuttle_speeds(mut self, speeds: &[f32]) -> JogConfig {
+        self.shuttle_speeds = speeds.to_vec();
+        self
+    }
+
+    pub fn shuttle_range(mut self, range: i32, deadzone: i32) -> JogConfig {
+        self.shuttle_range = range;
+        self.shuttle_deadzone = deadzone;
+        self
+    }
+
+    // Playback speed for an absolute position, 0 inside the dead zone
+    pub fn shuttle_speed(&self, position: i32) -> f32 {
+        let distance = position.unsigned_abs() as i64;
+        let deadzone = self.shuttle_deadzone.max(0) as i64;
+        let count = self.shuttle_speeds.len() as i64;
+        if distance <= deadzone || count == 0 {
+            return 0.0;
+        }
+
+        let span = (self.shuttle_range as i64 - deadzone).max(1);
+        let index = ((distance - deadzone - 1) * count / span).min(count - 1);
+        let speed = self.shuttle_speeds[index as usize];
+        if position < 0 {
+            -speed
+        } else {
+            speed
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct JogMotion {
+    pub mode: JogMode,
+    // The value as reported by the panel
+    pub raw: i32,
+    // Movement since the last report, in steps for the relative modes (accelerated)
+    // and in raw units for the absolute modes
+    pub delta: f64,
+    // Unaccelerated speed, in the same units as delta per second
+    pub velocity: f64,
+}
+
+// Turns jog reports into motion with velocity and acceleration, and the
+// shuttle position into discrete playback speeds.
+pub struct JogProcessor {
+    pub config: JogConfig,
+    mode: Option<JogMode>,
+    position: i32,
+    samples: VecDeque<(Instant, f64)>,
+    shuttle_speed: f32,
+}
+
+impl JogProcessor {
+    pub fn new(config: JogConfig) -> JogProcessor {
+        JogProcessor {
+            config,
+            mode: None,
+            position: 0,
+            samples: VecDeque::new(),
+            shuttle_speed: 0.0,
+        }
+    }
+
+    pub fn shuttle_speed(&self) -> f32 {
+        self.shuttle_speed
+    }
+
+    // Returns the motion and, if it changed, the new shuttle speed
+    pub fn process(&mut self, mode: JogMode, raw: i32, now: Instant) -> (JogMotion, Option<f32>) {
+        if self.mode != Some(mode) {
+            // Absolute positions restart from zero when the mode is set
+            self.mode = Some(mode);
+            self.position = 0;
+            self.samples.clear();
+        }
+
+        let movement = if mode.is_absolute() {
+            // Widened, as the panel may report any i32
+            let movement = (raw as i64 - self.position as i64) as f64;
+            self.position = raw;
+            movement
+        } else {
+            raw as f64 / RELATIVE_STEP
+        };
+
+        self.samples.push_back((now, movement));
+        while let Some(&(at, _)) = self.samples.front() {
+            if now.duration_since(at) > self.config.velocity_window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+        let window = self.config.velocity_window.as_secs_f64();
+        let velocity = if window > 0.0 {
+            self.samples.iter().map(|&(_, m)| m).sum::<f64>() / window
+        } else {
+            0.0
+        };
+
+        let delta = if mode.is_absolute() {
+            movement
+        } else {
+            movement * self.config.curve.gain(velocity)
+        };
+
+        let speed = if mode.is_absolute() {
+            self.config.shuttle_speed(raw)
+        } else {
+            0.0
+        };
+        let changed = if speed != self.shuttle_speed {
+            self.shuttle_speed = speed;
+            Some(speed)
+        } else {
+            None
+        };
+
+        let motion = JogMotion {
+            mode,
+            raw,
+            delta,
+            velocity,
+        };
+        (motion, changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccelCurve, JogConfig, JogMode, JogProcessor};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn relative_motion_keeps_full_resolution() {
+        let mut jog = JogProcessor::new(JogConfig::new());
+        let (motion, shuttle) = jog.process(JogMode::Relative, 540, Instant::now());
+
+        assert_eq!(motion.delta, 1.5);
+        assert_eq!(shuttle, None);
+    }
+
+    #[test]
+    fn curve_accelerates_fast_movement() {
+        let config = JogConfig::new()
+            .velocity_window(Duration::from_secs(1))
+            .curve(AccelCurve::Linear { factor: 0.5 });
+        let mut jog = JogProcessor::new(config);
+        let now = Instant::now();
+
+        let (motion, _) = jog.process(JogMode::Relative, 720, now);
+        assert_eq!(motion.velocity, 2.0);
+        assert_eq!(motion.delta, 4.0);
+
+        let (motion, _) = jog.process(JogMode::Relative, 720, now + Duration::from_millis(500));
+        assert_eq!(motion.velocity, 4.0);
+        assert_eq!(motion.delta, 6.0);
+    }
+
+    #[test]
+    fn shuttle_position_maps_to_speeds() {
+        let config = JogConfig::new()
+            .shuttle_speeds(&[1.0, 2.0])
+            .shuttle_range(1000, 100);
+        assert_eq!(config.shuttle_speed(50), 0.0);
+        assert_eq!(config.shuttle_speed(101), 1.0);
+        assert_eq!(config.shuttle_speed(-600), -2.0);
+        assert_eq!(config.shuttle_speed(5000), 2.0);
+
+        let mut jog = JogProcessor::new(config);
+        let now = Instant::now();
+        assert_eq!(jog.process(JogMode::Absolute, 200, now).1, Some(1.0));
+        assert_eq!(jog.process(JogMode::Absolute, 300, now).1, None);
+        assert_eq!(jog.process(JogMode::Absolute, -800, now).1, Some(-2.0));
+        assert_eq!(jog.process(JogMode::Relative, 360, now).1, Some(0.0));
+    }
+
+    #[test]
+    fn extreme_absolute_positions_do_not_overflow() {
+        let mut jog = JogProcessor::new(JogConfig::new());
+        let now = Instant::now();
+
+        jog.process(JogMode::Absolute, i32::MAX, now);
+        let (motion, _) = jog.process(JogMode::Absolute, i32::MIN, now);
+        assert_eq!(motion.delta, -(u32::MAX as f64));
+    }
+
+    #[test]
+    fn invalid_linear_factors_are_rejected() {
+        for factor in [-0.5, f64::NAN] {
+            let config = JogConfig::new().curve(AccelCurve::Linear { factor });
+            assert_eq!(config.curve, AccelCurve::Flat);
+            assert_eq!(AccelCurve::Linear { factor }.gain(10.0), 1.0);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use num_enum::TryFromPrimitive;
+use std::fmt;
+use strum_macros::{EnumIter, EnumString};
+
+use super::JogMode;
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, PartialOrd, TryFromPrimitive, Debug, EnumIter, EnumString)]
+pub enum JogLed {
+    Jog = 0,
+    Shtl = 1,
+    Scrl = 2,
+}
+
+impl JogLed {
+    // The indicator lit for a jog mode when the LEDs follow the active mode
+    pub fn for_mode(mode: JogMode) -> JogLed {
+        match mode {
+            JogMode::Relative => JogLed::Jog,
+            JogMode::Absolute | JogMode::AbsoluteDeadzone => JogLed::Shtl,
+            JogMode::RelativeAlt => JogLed::Scrl,
+        }
+    }
+}
+
+impl fmt::Display for JogLed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
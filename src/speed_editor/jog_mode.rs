@@ -0,0 +1,38 @@
+use num_enum::TryFromPrimitive;
+use std::{fmt, str::FromStr};
+use strum_macros::{EnumIter, EnumString};
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, PartialOrd, TryFromPrimitive, Debug, EnumIter, EnumString)]
+pub enum JogMode {
+    // Reports the movement since the last report
+    Relative = 0,
+    // Reports the position since the mode was set, about -4096..4096 for half a turn
+    Absolute = 1,
+    // Behaves like Relative
+    RelativeAlt = 2,
+    // Same as Absolute with a small dead band around zero
+    AbsoluteDeadzone = 3,
+}
+
+impl JogMode {
+    pub fn is_absolute(&self) -> bool {
+        matches!(self, JogMode::Absolute | JogMode::AbsoluteDeadzone)
+    }
+
+    // A variant name, or jog, shuttle and scroll as printed on the panel
+    pub fn from_name(name: &str) -> Option<JogMode> {
+        match name.to_lowercase().as_str() {
+            "jog" => Some(JogMode::Relative),
+            "shuttle" => Some(JogMode::Absolute),
+            "scroll" => Some(JogMode::RelativeAlt),
+            _ => JogMode::from_str(name).ok(),
+        }
+    }
+}
+
+impl fmt::Display for JogMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
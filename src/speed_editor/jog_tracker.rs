@@ -0,0 +1,111 @@
+use super::JogMode;
+
+// Accumulates the wheel position in raw units (ticks) and turns it into
+// steps of `ticks_per_step`, keeping the remainder between reports.
+#[derive(Clone, Debug)]
+pub struct JogTracker {
+    pub ticks_per_step: i64,
+    // Soft limits on the position, relative to the origin
+    pub limits: Option<(i64, i64)>,
+    position: i64,
+    mode: Option<JogMode>,
+    last_raw: i32,
+}
+
+impl JogTracker {
+    // 360 ticks is one step of the Relative mode
+    pub fn new(ticks_per_step: i64) -> JogTracker {
+        JogTracker {
+            ticks_per_step: ticks_per_step.max(1),
+            limits: None,
+            position: 0,
+            mode: None,
+            last_raw: 0,
+        }
+    }
+
+    pub fn limits(mut self, min: i64, max: i64) -> JogTracker {
+        self.limits = Some((min.min(max), min.max(max)));
+        self.position = self.clamp(self.position);
+        self
+    }
+
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    // Make the current position the origin
+    pub fn reset(&mut self) {
+        self.position = 0;
+    }
+
+    // Returns the number of steps taken, negative when turning backwards
+    pub fn feed(&mut self, mode: JogMode, raw: i32) -> i64 {
+        let delta = if mode.is_absolute() {
+            // Absolute positions restart from zero when the mode is set
+            let previous = if self.mode == Some(mode) {
+                self.last_raw
+            } else {
+                0
+            };
+            self.last_raw = raw;
+            raw as i64 - previous as i64
+        } else {
+            raw as i64
+        };
+        self.mode = Some(mode);
+
+        // Steps are counted at every multiple of ticks_per_step crossed
+        let position = self.clamp(self.position + delta);
+        let steps = position.div_euclid(self.ticks_per_step)
+            - self.position.div_euclid(self.ticks_per_step);
+        self.position = position;
+        steps
+    }
+
+    fn clamp(&self, position: i64) -> i64 {
+        match self.limits {
+            Some((min, max)) => position.clamp(min, max),
+            None => position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JogMode, JogTracker};
+
+    #[test]
+    fn remainder_is_kept_between_reports() {
+        let mut tracker = JogTracker::new(100);
+
+        assert_eq!(tracker.feed(JogMode::Relative, 60), 0);
+        assert_eq!(tracker.feed(JogMode::Relative, 60), 1);
+        assert_eq!(tracker.feed(JogMode::Relative, 250), 2);
+        assert_eq!(tracker.feed(JogMode::Relative, -80), -1);
+        assert_eq!(tracker.position(), 290);
+    }
+
+    #[test]
+    fn absolute_reports_are_turned_into_movement() {
+        let mut tracker = JogTracker::new(100);
+
+        assert_eq!(tracker.feed(JogMode::Absolute, 250), 2);
+        assert_eq!(tracker.feed(JogMode::Absolute, 300), 1);
+        assert_eq!(tracker.feed(JogMode::Absolute, 0), -3);
+        assert_eq!(tracker.position(), 0);
+    }
+
+    #[test]
+    fn soft_limits_stop_stepping() {
+        let mut tracker = JogTracker::new(100).limits(-150, 150);
+
+        assert_eq!(tracker.feed(JogMode::Relative, 1000), 1);
+        assert_eq!(tracker.position(), 150);
+        assert_eq!(tracker.feed(JogMode::Relative, -100), -1);
+
+        tracker.reset();
+        assert_eq!(tracker.position(), 0);
+        assert_eq!(tracker.feed(JogMode::Relative, 150), 1);
+    }
+}
@@ -1,9 +1,9 @@
 use num_enum::TryFromPrimitive;
 use std::fmt;
-use strum_macros::EnumIter;
+use strum_macros::{EnumIter, EnumString};
 
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, PartialOrd, TryFromPrimitive, Debug, EnumIter)]
+#[derive(Clone, Copy, PartialEq, PartialOrd, TryFromPrimitive, Debug, EnumIter, EnumString)]
 pub enum KeyLed {
     CloseUp = 0,
 
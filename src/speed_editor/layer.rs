@@ -0,0 +1,179 @@
+use super::{Key, KeyLed};
+
+// Name of the layer active when no other layer is
+pub const BASE_LAYER: &str = "base";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LayerSwitch {
+    // Active while the key is held
+    Momentary,
+    // Each press turns the layer on or off
+    Toggle,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Layer {
+    pub name: String,
+    // Lit while the layer is active
+    pub leds: Vec<KeyLed>,
+}
+
+// Layers stacked on top of the base layer, the last one activated wins.
+// Switch keys are taken out of the key events.
+#[derive(Clone, Debug)]
+pub struct LayerStack {
+    layers: Vec<Layer>,
+    switches: Vec<(Key, usize, LayerSwitch)>,
+    active: Vec<usize>,
+}
+
+impl Default for LayerStack {
+    fn default() -> LayerStack {
+        LayerStack {
+            layers: vec![Layer {
+                name: BASE_LAYER.to_string(),
+                leds: vec![],
+            }],
+            switches: vec![],
+            active: vec![],
+        }
+    }
+}
+
+impl LayerStack {
+    pub fn new() -> LayerStack {
+        LayerStack::default()
+    }
+
+    // Add a layer, or replace the LED preset of an existing one
+    pub fn add_layer(&mut self, name: &str, leds: Vec<KeyLed>) {
+        let index = self.index(name);
+        self.layers[index].leds = leds;
+    }
+
+    pub fn momentary(&mut self, key: Key, name: &str) {
+        self.add_switch(key, name, LayerSwitch::Momentary);
+    }
+
+    pub fn toggle(&mut self, key: Key, name: &str) {
+        self.add_switch(key, name, LayerSwitch::Toggle);
+    }
+
+    pub fn add_switch(&mut self, key: Key, name: &str, switch: LayerSwitch) {
+        let index = self.index(name);
+        self.switches.retain(|(k, _, _)| *k != key);
+        self.switches.push((key, index, switch));
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn active(&self) -> &Layer {
+        &self.layers[self.active_index()]
+    }
+
+    pub fn is_switch(&self, key: Key) -> bool {
+        self.switches.iter().any(|(k, _, _)| *k == key)
+    }
+
+    // Back to the base layer
+    pub fn reset(&mut self) {
+        self.active.clear();
+    }
+
+    pub(crate) fn active_index(&self) -> usize {
+        self.active.last().copied().unwrap_or(0)
+    }
+
+    pub(crate) fn layer(&self, index: usize) -> &Layer {
+        &self.layers[index]
+    }
+
+    // Returns false for keys that are not layer switches
+    pub(crate) fn key_down(&mut self, key: Key) -> bool {
+        match self.switch(key) {
+            Some((index, LayerSwitch::Momentary)) => {
+                self.active.retain(|&i| i != index);
+                self.active.push(index);
+                true
+            }
+            Some((index, LayerSwitch::Toggle)) => {
+                if self.active.contains(&index) {
+                    self.active.retain(|&i| i != index);
+                } else {
+                    self.active.push(index);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn key_up(&mut self, key: Key) -> bool {
+        match self.switch(key) {
+            Some((index, LayerSwitch::Momentary)) => {
+                self.active.retain(|&i| i != index);
+                true
+            }
+            Some((_, LayerSwitch::Toggle)) => true,
+            None => false,
+        }
+    }
+
+    fn switch(&self, key: Key) -> Option<(usize, LayerSwitch)> {
+        self.switches
+            .iter()
+            .find(|(k, _, _)| *k == key)
+            .map(|&(_, index, switch)| (index, switch))
+    }
+
+    fn index(&mut self, name: &str) -> usize {
+        match self.layers.iter().position(|l| l.name == name) {
+            Some(index) => index,
+            None => {
+                self.layers.push(Layer {
+                    name: name.to_string(),
+                    leds: vec![],
+                });
+                self.layers.len() - 1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, LayerStack, BASE_LAYER};
+
+    #[test]
+    fn momentary_layer_is_active_while_held() {
+        let mut layers = LayerStack::new();
+        layers.momentary(Key::Source, "color");
+
+        assert!(layers.key_down(Key::Source));
+        assert_eq!(layers.active().name, "color");
+        assert!(layers.key_up(Key::Source));
+        assert_eq!(layers.active().name, BASE_LAYER);
+        assert!(!layers.key_down(Key::Cut));
+    }
+
+    #[test]
+    fn toggled_layers_stack() {
+        let mut layers = LayerStack::new();
+        layers.toggle(Key::Timeline, "trim");
+        layers.momentary(Key::Source, "color");
+
+        layers.key_down(Key::Timeline);
+        layers.key_up(Key::Timeline);
+        assert_eq!(layers.active().name, "trim");
+
+        layers.key_down(Key::Source);
+        assert_eq!(layers.active().name, "color");
+        layers.key_up(Key::Source);
+        assert_eq!(layers.active().name, "trim");
+
+        layers.key_down(Key::Timeline);
+        assert_eq!(layers.active().name, BASE_LAYER);
+    }
+}
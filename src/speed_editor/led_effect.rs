@@ -0,0 +1,260 @@
+use std::time::{Duration, Instant};
+
+use super::KeyLed;
+
+// The camera keys in reading order, as lit by LedEffect::cam_chase()
+pub const CAM_LEDS: [KeyLed; 9] = [
+    KeyLed::Cam1,
+    KeyLed::Cam2,
+    KeyLed::Cam3,
+    KeyLed::Cam4,
+    KeyLed::Cam5,
+    KeyLed::Cam6,
+    KeyLed::Cam7,
+    KeyLed::Cam8,
+    KeyLed::Cam9,
+];
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum LedEffect {
+    // Lit for `on`, dark for `off`, repeated until removed
+    Blink {
+        leds: Vec<KeyLed>,
+        on: Duration,
+        off: Duration,
+    },
+    // One LED at a time, moving to the next every `step`
+    Chase {
+        leds: Vec<KeyLed>,
+        step: Duration,
+    },
+    // Lit once for `duration`, then removed
+    Flash {
+        leds: Vec<KeyLed>,
+        duration: Duration,
+    },
+}
+
+impl LedEffect {
+    pub fn blink(leds: Vec<KeyLed>, period: Duration) -> LedEffect {
+        LedEffect::Blink {
+            leds,
+            on: period / 2,
+            off: period - period / 2,
+        }
+    }
+
+    // A short blip every period
+    pub fn pulse(leds: Vec<KeyLed>, period: Duration) -> LedEffect {
+        LedEffect::Blink {
+            leds,
+            on: period / 8,
+            off: period - period / 8,
+        }
+    }
+
+    pub fn chase(leds: Vec<KeyLed>, step: Duration) -> LedEffect {
+        LedEffect::Chase { leds, step }
+    }
+
+    pub fn cam_chase(step: Duration) -> LedEffect {
+        LedEffect::chase(CAM_LEDS.to_vec(), step)
+    }
+
+    pub fn flash(leds: Vec<KeyLed>, duration: Duration) -> LedEffect {
+        LedEffect::Flash { leds, duration }
+    }
+
+    pub fn leds(&self) -> &[KeyLed] {
+        match self {
+            LedEffect::Blink { leds, .. }
+            | LedEffect::Chase { leds, .. }
+            | LedEffect::Flash { leds, .. } => leds,
+        }
+    }
+
+    // LEDs lit `elapsed` after the effect started
+    fn lit(&self, elapsed: Duration) -> Vec<KeyLed> {
+        match self {
+            LedEffect::Blink { leds, on, off } => {
+                if nanos(elapsed) % nanos(*on + *off).max(1) < nanos(*on) {
+                    leds.clone()
+                } else {
+                    vec![]
+                }
+            }
+            LedEffect::Chase { leds, step } => {
+                if leds.is_empty() {
+                    return vec![];
+                }
+                let index = nanos(elapsed) / nanos(*step).max(1) % leds.len() as u128;
+                vec![leds[index as usize]]
+            }
+            LedEffect::Flash { leds, duration } => {
+                if elapsed < *duration {
+                    leds.clone()
+                } else {
+                    vec![]
+                }
+            }
+        }
+    }
+
+    // Time from the start of the effect to its next change
+    fn next_change(&self, elapsed: Duration) -> Option<Duration> {
+        let elapsed = nanos(elapsed);
+        let next = match self {
+            LedEffect::Blink { on, off, .. } => {
+                let cycle = nanos(*on + *off).max(1);
+                let start = elapsed - elapsed % cycle;
+                if elapsed - start < nanos(*on) {
+                    start + nanos(*on)
+                } else {
+                    start + cycle
+                }
+            }
+            LedEffect::Chase { step, .. } => {
+                let step = nanos(*step).max(1);
+                (elapsed / step + 1) * step
+            }
+            LedEffect::Flash { duration, .. } => {
+                if elapsed >= nanos(*duration) {
+                    return None;
+                }
+                nanos(*duration)
+            }
+        };
+        Some(Duration::from_nanos(next.min(u64::MAX as u128) as u64))
+    }
+}
+
+fn nanos(duration: Duration) -> u128 {
+    duration.as_nanos()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct EffectId(u64);
+
+// Running effects. An LED used by an effect follows the effect, the others
+// keep their static state.
+#[derive(Clone, Default, Debug)]
+pub struct LedEffects {
+    effects: Vec<(EffectId, LedEffect, Instant)>,
+    next_id: u64,
+}
+
+impl LedEffects {
+    pub fn new() -> LedEffects {
+        LedEffects::default()
+    }
+
+    pub fn add(&mut self, effect: LedEffect, now: Instant) -> EffectId {
+        let id = EffectId(self.next_id);
+        self.next_id += 1;
+        self.effects.push((id, effect, now));
+        id
+    }
+
+    pub fn remove(&mut self, id: EffectId) {
+        self.effects.retain(|(i, _, _)| *i != id);
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    // Drop the flashes that are over
+    pub fn expire(&mut self, now: Instant) {
+        self.effects.retain(|(_, effect, started)| {
+            effect
+                .next_change(now.saturating_duration_since(*started))
+                .is_some()
+        });
+    }
+
+    // The static LEDs with the effects applied on top
+    pub fn apply(&self, leds: &[KeyLed], now: Instant) -> Vec<KeyLed> {
+        let controlled: Vec<KeyLed> = self
+            .effects
+            .iter()
+            .flat_map(|(_, effect, _)| effect.leds().iter().copied())
+            .collect();
+
+        let mut lit: Vec<KeyLed> = leds
+            .iter()
+            .filter(|led| !controlled.contains(led))
+            .copied()
+            .collect();
+        for (_, effect, started) in self.effects.iter() {
+            for led in effect.lit(now.saturating_duration_since(*started)) {
+                if !lit.contains(&led) {
+                    lit.push(led);
+                }
+            }
+        }
+        lit
+    }
+
+    pub fn next_deadline(&self, now: Instant) -> Option<Instant> {
+        self.effects
+            .iter()
+            .filter_map(|(_, effect, started)| {
+                effect
+                    .next_change(now.saturating_duration_since(*started))
+                    .map(|next| *started + next)
+            })
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyLed, LedEffect, LedEffects};
+    use std::time::{Duration, Instant};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn blink_overrides_static_state() {
+        let mut effects = LedEffects::new();
+        let start = Instant::now();
+        effects.add(LedEffect::blink(vec![KeyLed::Cut], ms(100)), start);
+
+        let leds = [KeyLed::Cut, KeyLed::Dis];
+        assert_eq!(effects.apply(&leds, start), vec![KeyLed::Dis, KeyLed::Cut]);
+        assert_eq!(effects.apply(&leds, start + ms(60)), vec![KeyLed::Dis]);
+        assert_eq!(effects.next_deadline(start + ms(60)), Some(start + ms(100)));
+    }
+
+    #[test]
+    fn chase_moves_across_the_cam_keys() {
+        let mut effects = LedEffects::new();
+        let start = Instant::now();
+        effects.add(LedEffect::cam_chase(ms(10)), start);
+
+        assert_eq!(effects.apply(&[], start), vec![KeyLed::Cam1]);
+        assert_eq!(effects.apply(&[], start + ms(25)), vec![KeyLed::Cam3]);
+        assert_eq!(effects.apply(&[], start + ms(95)), vec![KeyLed::Cam1]);
+        assert_eq!(effects.next_deadline(start + ms(25)), Some(start + ms(30)));
+    }
+
+    #[test]
+    fn flash_expires() {
+        let mut effects = LedEffects::new();
+        let start = Instant::now();
+        effects.add(LedEffect::flash(vec![KeyLed::Snap], ms(50)), start);
+
+        assert_eq!(effects.apply(&[], start), vec![KeyLed::Snap]);
+        assert_eq!(effects.next_deadline(start), Some(start + ms(50)));
+
+        effects.expire(start + ms(50));
+        assert!(effects.is_empty());
+        assert_eq!(effects.next_deadline(start + ms(50)), None);
+    }
+}
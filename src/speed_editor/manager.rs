@@ -0,0 +1,192 @@
+use std::{
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use super::{controller::Controller, Event, SpeedEditor, SpeedEditorError, SpeedEditorResult};
+
+pub type DeviceId = String;
+
+struct ManagedDevice {
+    id: DeviceId,
+    controller: Controller,
+    handle: JoinHandle<SpeedEditorResult>,
+}
+
+// Runs several panels on their own threads and merges their events,
+// each tagged with the id it was added under.
+pub struct DeviceManager {
+    events_tx: Sender<(DeviceId, Event)>,
+    events: Receiver<(DeviceId, Event)>,
+    devices: Vec<ManagedDevice>,
+}
+
+impl Default for DeviceManager {
+    fn default() -> DeviceManager {
+        let (events_tx, events) = channel();
+        DeviceManager {
+            events_tx,
+            events,
+            devices: vec![],
+        }
+    }
+}
+
+impl DeviceManager {
+    const POLL_INTERVAL: u64 = 20;
+
+    pub fn new() -> DeviceManager {
+        DeviceManager::default()
+    }
+
+    pub fn add(&mut self, id: &str, speed_editor: SpeedEditor) -> Controller {
+        let tx = self.events_tx.clone();
+        let tag = id.to_string();
+        let (controller, handle) =
+            speed_editor.spawn_with(move |event| tx.send((tag.clone(), event)).is_ok());
+
+        self.devices.push(ManagedDevice {
+            id: id.to_string(),
+            controller: controller.clone(),
+            handle,
+        });
+        controller
+    }
+
+    // Open every connected Speed Editor, using DeviceInfo::id() as the device id
+    #[cfg(feature = "hidapi")]
+    pub fn add_all(&mut self) -> Result<Vec<DeviceId>, SpeedEditorError> {
+        let mut ids = vec![];
+        for info in super::transport::list_devices()? {
+            let id = info.id();
+            if ids.contains(&id) || self.controller(&id).is_some() {
+                continue;
+            }
+
+            let connector = match &info.serial_number {
+                Some(serial) if !serial.is_empty() => super::HidConnector::serial(serial),
+                _ => super::HidConnector::path(&info.path),
+            };
+            self.add(&id, crate::with_connector(connector)?);
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    pub fn ids(&self) -> Vec<DeviceId> {
+        self.devices.iter().map(|d| d.id.clone()).collect()
+    }
+
+    pub fn controller(&self, id: &str) -> Option<Controller> {
+        self.devices
+            .iter()
+            .find(|d| d.id == id)
+            .map(|d| d.controller.clone())
+    }
+
+    pub fn poll_event(&self, timeout: Duration) -> Option<(DeviceId, Event)> {
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    // Ends once every panel thread has finished, e.g. after stopping all
+    // their controllers, and its events were received
+    pub fn events(&self) -> impl Iterator<Item = (DeviceId, Event)> + '_ {
+        std::iter::from_fn(move || loop {
+            let timeout = Duration::from_millis(Self::POLL_INTERVAL);
+            match self.events.recv_timeout(timeout) {
+                Ok(event) => return Some(event),
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.devices.iter().all(|d| d.handle.is_finished()) {
+                        // Events sent just before the last thread finished
+                        return self.events.try_recv().ok();
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        })
+    }
+
+    // Stop every panel and wait for them, returning the first error
+    pub fn stop(self) -> SpeedEditorResult {
+        for device in self.devices.iter() {
+            let _ = device.controller.stop();
+        }
+
+        let mut result = Ok(());
+        for device in self.devices {
+            let r = device
+                .handle
+                .join()
+                .unwrap_or(Err(SpeedEditorError::Stopped));
+            if result.is_ok() {
+                result = r;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeviceManager;
+    use crate::{
+        speed_editor::{mock::fixtures::connecting, SpeedEditor},
+        Event, Key, MockTransport,
+    };
+    use std::time::Duration;
+
+    // A panel that presses `key` once connected
+    fn pressing(key: u8) -> SpeedEditor {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        transport.push_input_report(&[0x4, key, 0x0]);
+        se
+    }
+
+    #[test]
+    fn events_are_tagged_by_device() {
+        let mut manager = DeviceManager::new();
+        manager.add("left", pressing(0x7));
+        manager.add("right", pressing(0x8));
+
+        let mut key_downs = vec![];
+        while key_downs.len() < 2 {
+            if let Some((id, Event::KeyDown(key))) = manager.poll_event(Duration::from_secs(1)) {
+                key_downs.push((id, key));
+            }
+        }
+        key_downs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(
+            key_downs,
+            vec![
+                ("left".to_string(), Key::In),
+                ("right".to_string(), Key::Out)
+            ]
+        );
+        assert_eq!(manager.ids(), vec!["left", "right"]);
+        assert!(manager.controller("left").is_some());
+        manager.stop().unwrap();
+    }
+
+    #[test]
+    fn events_end_once_every_panel_stopped() {
+        let mut manager = DeviceManager::new();
+        let controller = manager.add("left", pressing(0x7));
+
+        let mut key_downs = vec![];
+        for (_, event) in manager.events() {
+            if let Event::KeyDown(key) = event {
+                key_downs.push(key);
+                controller.stop().unwrap();
+            }
+        }
+
+        assert_eq!(key_downs, vec![Key::In]);
+        manager.stop().unwrap();
+    }
+}
@@ -0,0 +1,579 @@
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use super::{
+    command::Command, Event, JogTracker, Key, KeyLed, SpeedEditor, SpeedEditorResult, StopToken,
+};
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const CONTROL_CHANGE: u8 = 0xb0;
+
+// What a key sends
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MidiMapping {
+    // Note on with velocity 127, note off on release
+    Note(u8),
+    // 127 on press, 0 on release
+    Cc(u8),
+}
+
+// How a jog delta is written into a relative CC value
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RelativeEncoding {
+    // 1 is +1, 127 is -1
+    TwosComplement,
+    // 65 is +1, 63 is -1
+    BinaryOffset,
+}
+
+impl RelativeEncoding {
+    pub fn encode(&self, delta: i8) -> u8 {
+        let delta = delta.clamp(-63, 63);
+        match self {
+            RelativeEncoding::TwosComplement => (delta as u8) & 0x7f,
+            RelativeEncoding::BinaryOffset => (64 + delta) as u8,
+        }
+    }
+
+    pub fn decode(&self, value: u8) -> i8 {
+        let value = value & 0x7f;
+        match self {
+            RelativeEncoding::TwosComplement if value >= 64 => (value as i16 - 128) as i8,
+            RelativeEncoding::TwosComplement => value as i8,
+            RelativeEncoding::BinaryOffset => value as i8 - 64,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MidiConfig {
+    // 0 to 15
+    pub channel: u8,
+    // Keys not listed send the note of their key code
+    pub key_mappings: Vec<(Key, MidiMapping)>,
+    pub send_key_up: bool,
+    // Relative CC the jog steps (Event::Step) are sent to, None to not send the wheel
+    pub jog_cc: Option<u8>,
+    pub jog_encoding: RelativeEncoding,
+    // Raw jog units per step, used to enable jog tracking when the panel has none
+    pub jog_ticks_per_step: i64,
+}
+
+impl Default for MidiConfig {
+    fn default() -> MidiConfig {
+        MidiConfig {
+            channel: 0,
+            key_mappings: vec![],
+            send_key_up: true,
+            jog_cc: Some(16),
+            jog_encoding: RelativeEncoding::TwosComplement,
+            jog_ticks_per_step: 360,
+        }
+    }
+}
+
+impl MidiConfig {
+    pub fn new() -> MidiConfig {
+        MidiConfig::default()
+    }
+
+    pub fn channel(mut self, channel: u8) -> MidiConfig {
+        self.channel = channel & 0x0f;
+        self
+    }
+
+    pub fn key_note(self, key: Key, note: u8) -> MidiConfig {
+        self.key_mapping(key, MidiMapping::Note(note & 0x7f))
+    }
+
+    pub fn key_cc(self, key: Key, cc: u8) -> MidiConfig {
+        self.key_mapping(key, MidiMapping::Cc(cc & 0x7f))
+    }
+
+    pub fn key_mapping(mut self, key: Key, mapping: MidiMapping) -> MidiConfig {
+        self.key_mappings.retain(|(k, _)| *k != key);
+        self.key_mappings.push((key, mapping));
+        self
+    }
+
+    pub fn send_key_up(mut self, send: bool) -> MidiConfig {
+        self.send_key_up = send;
+        self
+    }
+
+    pub fn jog(mut self, cc: Option<u8>, encoding: RelativeEncoding) -> MidiConfig {
+        self.jog_cc = cc.map(|cc| cc & 0x7f);
+        self.jog_encoding = encoding;
+        self
+    }
+
+    pub fn jog_ticks_per_step(mut self, ticks: i64) -> MidiConfig {
+        self.jog_ticks_per_step = ticks.max(1);
+        self
+    }
+
+    pub fn mapping(&self, key: Key) -> MidiMapping {
+        match self.key_mappings.iter().find(|(k, _)| *k == key) {
+            Some((_, mapping)) => *mapping,
+            None => MidiMapping::Note(key as u8),
+        }
+    }
+
+    // The key a received note or CC belongs to
+    pub fn key(&self, mapping: MidiMapping) -> Option<Key> {
+        if let Some((key, _)) = self.key_mappings.iter().find(|(_, m)| *m == mapping) {
+            return Some(*key);
+        }
+        match mapping {
+            MidiMapping::Note(note) => match Key::try_from(note) {
+                Ok(key)
+                    if key != Key::None && !self.key_mappings.iter().any(|(k, _)| *k == key) =>
+                {
+                    Some(key)
+                }
+                _ => None,
+            },
+            MidiMapping::Cc(_) => None,
+        }
+    }
+
+    fn key_message(&self, key: Key, down: bool) -> Vec<u8> {
+        match (self.mapping(key), down) {
+            (MidiMapping::Note(note), true) => vec![NOTE_ON | self.channel, note, 127],
+            (MidiMapping::Note(note), false) => vec![NOTE_OFF | self.channel, note, 0],
+            (MidiMapping::Cc(cc), down) => {
+                vec![
+                    CONTROL_CHANGE | self.channel,
+                    cc,
+                    if down { 127 } else { 0 },
+                ]
+            }
+        }
+    }
+
+    // The command for an incoming message: a note or CC mapped to a key lights
+    // that key's LED, note off, velocity 0 or a CC below 64 turns it off.
+    // Messages on other channels are ignored.
+    pub(crate) fn command(&self, message: &[u8]) -> Option<Command> {
+        let (&status, data) = message.split_first()?;
+        if status & 0x0f != self.channel || data.len() < 2 {
+            return None;
+        }
+        let (mapping, on) = match status & 0xf0 {
+            NOTE_ON => (MidiMapping::Note(data[0]), data[1] > 0),
+            NOTE_OFF => (MidiMapping::Note(data[0]), false),
+            CONTROL_CHANGE => (MidiMapping::Cc(data[0]), data[1] >= 64),
+            _ => return None,
+        };
+        let key = self.key(mapping)?;
+        let led = KeyLed::from_str(&key.to_string()).ok()?;
+        Some(Command::KeyLed(led, on))
+    }
+}
+
+// Where MIDI messages go and come from
+pub trait MidiBackend: Send {
+    fn send(&mut self, message: &[u8]) -> SpeedEditorResult;
+
+    // Messages received since the last call, each one complete
+    fn receive(&mut self) -> Vec<Vec<u8>>;
+}
+
+// Keeps the sent bytes and hands out pushed messages, clones share the buffers
+#[derive(Clone, Default, Debug)]
+pub struct ByteSink {
+    sent: Arc<Mutex<Vec<u8>>>,
+    incoming: Arc<Mutex<VecDeque<Vec<u8>>>>,
+}
+
+impl ByteSink {
+    pub fn new() -> ByteSink {
+        ByteSink::default()
+    }
+
+    pub fn sent(&self) -> Vec<u8> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    pub fn push(&self, message: &[u8]) {
+        self.incoming.lock().unwrap().push_back(message.to_vec());
+    }
+}
+
+impl MidiBackend for ByteSink {
+    fn send(&mut self, message: &[u8]) -> SpeedEditorResult {
+        self.sent.lock().unwrap().extend_from_slice(message);
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Vec<Vec<u8>> {
+        self.incoming.lock().unwrap().drain(..).collect()
+    }
+}
+
+// Sends panel events as MIDI and lights the key LEDs from the messages it receives
+pub struct MidiBridge<B: MidiBackend> {
+    pub config: MidiConfig,
+    backend: B,
+}
+
+impl<B: MidiBackend + 'static> MidiBridge<B> {
+    const POLL_INTERVAL: u64 = 10;
+
+    pub fn new(config: MidiConfig, backend: B) -> MidiBridge<B> {
+        MidiBridge { config, backend }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    // The messages sent for an event. The wheel is sent from the Step events
+    // of jog tracking, which counts the raw reports.
+    pub fn messages(&self, event: &Event) -> Vec<Vec<u8>> {
+        match event {
+            Event::KeyDown(key) => vec![self.config.key_message(*key, true)],
+            Event::KeyUp(key) if self.config.send_key_up => {
+                vec![self.config.key_message(*key, false)]
+            }
+            Event::Step(steps) => match self.config.jog_cc {
+                Some(cc) => vec![vec![
+                    CONTROL_CHANGE | self.config.channel,
+                    cc,
+                    self.config
+                        .jog_encoding
+                        .encode((*steps).clamp(-63, 63) as i8),
+                ]],
+                None => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    pub fn send(&mut self, event: &Event) -> SpeedEditorResult {
+        for message in self.messages(event) {
+            self.backend.send(&message)?;
+        }
+        Ok(())
+    }
+
+    pub fn run(self, speed_editor: SpeedEditor) -> SpeedEditorResult {
+        self.run_until(speed_editor, &StopToken::new())
+    }
+
+    // With `jog_cc` set, this enables jog tracking on `speed_editor` with
+    // `jog_ticks_per_step`. A panel that already tracks the jog keeps its
+    // tracker, and its steps are sent with its ticks and limits. The panel keeps
+    // running when a MIDI send fails, the first failure is returned once the
+    // bridge stops.
+    pub fn run_until(self, mut speed_editor: SpeedEditor, token: &StopToken) -> SpeedEditorResult {
+        if self.config.jog_cc.is_some() && speed_editor.jog_tracker.is_none() {
+            speed_editor.enable_jog_tracking(JogTracker::new(self.config.jog_ticks_per_step));
+        }
+        let config = self.config.clone();
+        let bridge = Arc::new(Mutex::new(self));
+        let sender = bridge.clone();
+        let failed = Arc::new(Mutex::new(None));
+        let failure = failed.clone();
+        let on_event = move |event| {
+            if let Err(e) = sender.lock().unwrap().send(&event) {
+                failure.lock().unwrap().get_or_insert(e);
+            }
+            true
+        };
+
+        let interval = Duration::from_millis(Self::POLL_INTERVAL);
+        let result = speed_editor.run_bridge(token, interval, on_event, |controller| {
+            let received = bridge.lock().unwrap().backend.receive();
+            // Messages not mapped to a key are ignored
+            for command in received.iter().filter_map(|m| config.command(m)) {
+                controller.send(command)?;
+            }
+            Ok(!received.is_empty())
+        });
+        let failure = failed.lock().unwrap().take();
+        match failure {
+            Some(e) => result.and(Err(e)),
+            None => result,
+        }
+    }
+}
+
+#[cfg(feature = "midir")]
+pub use self::ports::MidirBackend;
+
+#[cfg(feature = "midir")]
+mod ports {
+    use midir::{MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+    use std::{
+        io,
+        sync::mpsc::{channel, Receiver},
+    };
+
+    use super::{MidiBackend, SpeedEditorResult};
+    use crate::SpeedEditorError;
+
+    fn error<E: std::fmt::Display>(e: E) -> SpeedEditorError {
+        io::Error::other(e.to_string()).into()
+    }
+
+    // MIDI ports of the system (ALSA, CoreMIDI or WinMM)
+    pub struct MidirBackend {
+        output: MidiOutputConnection,
+        _input: MidiInputConnection<()>,
+        incoming: Receiver<Vec<u8>>,
+    }
+
+    impl MidirBackend {
+        // Connect to the first output and input ports whose names contain `port`
+        pub fn connect(client: &str, port: &str) -> Result<MidirBackend, SpeedEditorError> {
+            let output = MidiOutput::new(client).map_err(error)?;
+            let out_port = output
+                .ports()
+                .into_iter()
+                .find(|p| output.port_name(p).is_ok_and(|n| n.contains(port)))
+                .ok_or_else(|| error(format!("no MIDI output port named {}", port)))?;
+            let input = MidiInput::new(client).map_err(error)?;
+            let in_port = input
+                .ports()
+                .into_iter()
+                .find(|p| input.port_name(p).is_ok_and(|n| n.contains(port)))
+                .ok_or_else(|| error(format!("no MIDI input port named {}", port)))?;
+
+            let (tx, incoming) = channel();
+            let _input = input
+                .connect(
+                    &in_port,
+                    client,
+                    move |_, message, _| {
+                        let _ = tx.send(message.to_vec());
+                    },
+                    (),
+                )
+                .map_err(error)?;
+            let output = output.connect(&out_port, client).map_err(error)?;
+
+            Ok(MidirBackend {
+                output,
+                _input,
+                incoming,
+            })
+        }
+
+        // Create an output and an input port named `port` other programs can
+        // connect to, e.g. an ALSA sequencer port
+        #[cfg(unix)]
+        pub fn virtual_port(client: &str, port: &str) -> Result<MidirBackend, SpeedEditorError> {
+            use midir::os::unix::{VirtualInput, VirtualOutput};
+
+            let (tx, incoming) = channel();
+            let _input = MidiInput::new(client)
+                .map_err(error)?
+                .create_virtual(
+                    port,
+                    move |_, message, _| {
+                        let _ = tx.send(message.to_vec());
+                    },
+                    (),
+                )
+                .map_err(error)?;
+            let output = MidiOutput::new(client)
+                .map_err(error)?
+                .create_virtual(port)
+                .map_err(error)?;
+
+            Ok(MidirBackend {
+                output,
+                _input,
+                incoming,
+            })
+        }
+    }
+
+    impl MidiBackend for MidirBackend {
+        fn send(&mut self, message: &[u8]) -> SpeedEditorResult {
+            self.output.send(message).map_err(error)
+        }
+
+        fn receive(&mut self) -> Vec<Vec<u8>> {
+            self.incoming.try_iter().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ByteSink, MidiBackend, MidiBridge, MidiConfig, MidiMapping, RelativeEncoding,
+        SpeedEditorResult,
+    };
+    use crate::{
+        speed_editor::{
+            command::Command,
+            mock::fixtures::{connecting, wait_until, CAM1_LIT},
+        },
+        Event, JogMode, Key, KeyLed, MockTransport, StopToken,
+    };
+    use std::{
+        io,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    // A port that went away, counting the messages it failed to send
+    #[derive(Clone, Default)]
+    struct Unplugged(Arc<AtomicUsize>);
+
+    impl MidiBackend for Unplugged {
+        fn send(&mut self, _: &[u8]) -> SpeedEditorResult {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Err(io::Error::other("unplugged").into())
+        }
+
+        fn receive(&mut self) -> Vec<Vec<u8>> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn keys_map_to_notes_and_ccs() {
+        let config = MidiConfig::new().channel(2).key_cc(Key::Cut, 20);
+        let bridge = MidiBridge::new(config, ByteSink::new());
+
+        assert_eq!(
+            bridge.messages(&Event::KeyDown(Key::In)),
+            vec![vec![0x92, 7, 127]]
+        );
+        assert_eq!(
+            bridge.messages(&Event::KeyUp(Key::In)),
+            vec![vec![0x82, 7, 0]]
+        );
+        assert_eq!(
+            bridge.messages(&Event::KeyDown(Key::Cut)),
+            vec![vec![0xb2, 20, 127]]
+        );
+        assert_eq!(bridge.config.key(MidiMapping::Cc(20)), Some(Key::Cut));
+        assert_eq!(bridge.config.key(MidiMapping::Note(15)), None);
+    }
+
+    #[test]
+    fn relative_encodings() {
+        let twos = RelativeEncoding::TwosComplement;
+        let offset = RelativeEncoding::BinaryOffset;
+
+        assert_eq!((twos.encode(1), twos.encode(-1)), (1, 127));
+        assert_eq!((offset.encode(1), offset.encode(-1)), (65, 63));
+        assert_eq!(twos.encode(-100), 65);
+        for delta in -63..=63 {
+            assert_eq!(twos.decode(twos.encode(delta)), delta);
+            assert_eq!(offset.decode(offset.encode(delta)), delta);
+        }
+    }
+
+    #[test]
+    fn jog_is_sent_in_steps() {
+        let config = MidiConfig::new().jog(Some(16), RelativeEncoding::BinaryOffset);
+        let bridge = MidiBridge::new(config, ByteSink::new());
+
+        assert!(bridge
+            .messages(&Event::Jog(JogMode::Relative, 1))
+            .is_empty());
+        assert_eq!(bridge.messages(&Event::Step(1)), vec![vec![0xb0, 16, 65]]);
+        assert_eq!(bridge.messages(&Event::Step(-1)), vec![vec![0xb0, 16, 63]]);
+    }
+
+    #[test]
+    fn one_detent_sends_one_cc() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        // Two half detents, then one detent back, in raw units
+        transport.push_input_report(&[0x3, 0x0, 0xb4, 0x0, 0x0, 0x0, 0x0]);
+        transport.push_input_report(&[0x3, 0x0, 0xb4, 0x0, 0x0, 0x0, 0x0]);
+        transport.push_input_report(&[0x3, 0x0, 0x98, 0xfe, 0xff, 0xff, 0x0]);
+
+        let sink = ByteSink::new();
+        let bridge = MidiBridge::new(MidiConfig::new(), sink.clone());
+        let token = StopToken::new();
+        let stop = token.clone();
+        let handle = thread::spawn(move || bridge.run_until(se, &stop));
+
+        wait_until(|| sink.sent().len() >= 6);
+
+        token.stop();
+        handle.join().unwrap().unwrap();
+        assert_eq!(sink.sent(), vec![0xb0, 16, 1, 0xb0, 16, 127]);
+    }
+
+    #[test]
+    fn incoming_messages_drive_key_leds() {
+        let config = MidiConfig::new().channel(1).key_cc(Key::Cut, 20);
+
+        assert_eq!(
+            config.command(&[0x91, 51, 100]),
+            Some(Command::KeyLed(KeyLed::Cam1, true))
+        );
+        assert_eq!(
+            config.command(&[0x91, 51, 0]),
+            Some(Command::KeyLed(KeyLed::Cam1, false))
+        );
+        assert_eq!(
+            config.command(&[0xb1, 20, 127]),
+            Some(Command::KeyLed(KeyLed::Cut, true))
+        );
+        assert_eq!(config.command(&[0x90, 51, 100]), None);
+        assert_eq!(config.command(&[0x91, 7, 100]), None);
+    }
+
+    #[test]
+    fn bridge_sends_events_and_drives_leds() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        transport.push_input_report(&[0x4, 0xf, 0x0]);
+
+        let sink = ByteSink::new();
+        let bridge = MidiBridge::new(MidiConfig::new(), sink.clone());
+        sink.push(&[0x90, 51, 127]);
+
+        let token = StopToken::new();
+        let stop = token.clone();
+        let handle = thread::spawn(move || bridge.run_until(se, &stop));
+
+        let lit = wait_until(|| {
+            transport.written().contains(&CAM1_LIT.to_vec()) && !sink.sent().is_empty()
+        });
+
+        token.stop();
+        handle.join().unwrap().unwrap();
+        assert!(lit);
+        assert_eq!(sink.sent(), vec![0x90, 15, 127]);
+    }
+
+    #[test]
+    fn failed_sends_are_returned_once_stopped() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        transport.push_input_report(&[0x4, 0xf, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0]);
+
+        let port = Unplugged::default();
+        let bridge = MidiBridge::new(MidiConfig::new(), port.clone());
+        let token = StopToken::new();
+        let stop = token.clone();
+        let handle = thread::spawn(move || bridge.run_until(se, &stop));
+
+        // The panel keeps running after the first failure
+        let failed = wait_until(|| port.0.load(Ordering::SeqCst) >= 2);
+
+        token.stop();
+        assert!(handle.join().unwrap().is_err());
+        assert!(failed);
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use super::{
+    transport::{Connector, Transport},
+    SpeedEditorError,
+};
+
+#[derive(Default)]
+struct MockState {
+    input_reports: VecDeque<Vec<u8>>,
+    feature_reports: VecDeque<Vec<u8>>,
+    written: Vec<Vec<u8>>,
+    sent_feature_reports: Vec<Vec<u8>>,
+    unplugged: bool,
+}
+
+// In-memory transport driven by scripted reports.
+// Clones share the same state, so a test can keep one to inspect what was written.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> MockTransport {
+        MockTransport::default()
+    }
+
+    // Queue a report returned by the next read_timeout() call.
+    pub fn push_input_report(&self, report: &[u8]) {
+        self.state().input_reports.push_back(report.to_vec());
+    }
+
+    // Queue a report returned by the next get_feature_report() call.
+    pub fn push_feature_report(&self, report: &[u8]) {
+        self.state().feature_reports.push_back(report.to_vec());
+    }
+
+    // Queue the replies of a successful authentication handshake.
+    pub fn push_auth_handshake(&self) {
+        self.push_feature_report(&[0x6, 0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8]);
+        self.push_feature_report(&[0x6, 0x2, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0]);
+        self.push_feature_report(&[0x6, 0x4, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0]);
+    }
+
+    // Once the queued input reports are consumed, reads fail as if the cable was pulled.
+    pub fn unplug(&self) {
+        self.state().unplugged = true;
+    }
+
+    pub fn written(&self) -> Vec<Vec<u8>> {
+        self.state().written.clone()
+    }
+
+    pub fn sent_feature_reports(&self) -> Vec<Vec<u8>> {
+        self.state().sent_feature_reports.clone()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Transport for MockTransport {
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, SpeedEditorError> {
+        let mut state = self.state();
+        match state.input_reports.pop_front() {
+            Some(report) => {
+                let len = report.len().min(buf.len());
+                buf[..len].copy_from_slice(&report[..len]);
+                Ok(len)
+            }
+            None if state.unplugged => {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "mock device unplugged").into())
+            }
+            None => {
+                // Yield a little instead of blocking for the whole timeout
+                drop(state);
+                thread::sleep(Duration::from_millis(timeout.clamp(0, 1) as u64));
+                Ok(0)
+            }
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, SpeedEditorError> {
+        self.state().written.push(data.to_vec());
+        Ok(data.len())
+    }
+
+    fn send_feature_report(&self, data: &[u8]) -> Result<(), SpeedEditorError> {
+        self.state().sent_feature_reports.push(data.to_vec());
+        Ok(())
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize, SpeedEditorError> {
+        match self.state().feature_reports.pop_front() {
+            Some(report) => {
+                let len = report.len().min(buf.len());
+                buf[..len].copy_from_slice(&report[..len]);
+                Ok(len)
+            }
+            None => {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no feature report queued").into())
+            }
+        }
+    }
+}
+
+// Hands out the queued transports one per successful connect.
+#[derive(Clone, Default)]
+pub struct MockConnector {
+    transports: Arc<Mutex<VecDeque<MockTransport>>>,
+}
+
+impl MockConnector {
+    pub fn new() -> MockConnector {
+        MockConnector::default()
+    }
+
+    pub fn push(&self, transport: MockTransport) {
+        self.transports.lock().unwrap().push_back(transport);
+    }
+}
+
+impl Connector for MockConnector {
+    fn open(&mut self) -> Result<Option<Box<dyn Transport>>, SpeedEditorError> {
+        Ok(self
+            .transports
+            .lock()
+            .unwrap()
+            .pop_front()
+            .map(|t| Box::new(t) as Box<dyn Transport>))
+    }
+}
+
+// Fixtures shared by the tests of the speed_editor modules
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::{MockConnector, MockTransport};
+    use crate::speed_editor::SpeedEditor;
+    use chrono::Utc;
+    use std::{thread, time::Duration};
+
+    // Key LED report with only Cam1 lit
+    pub(crate) const CAM1_LIT: [u8; 6] = [0x2, 0x0, 0x40, 0x0, 0x0, 0x0];
+
+    // A SpeedEditor that is already connected and authenticated over `transport`
+    pub(crate) fn authenticated(transport: &MockTransport) -> SpeedEditor {
+        let mut se = crate::with_connector(MockConnector::new()).unwrap();
+        se.device = Some(Box::new(transport.clone()));
+        se.last_authenticated_at = Some(Utc::now());
+        se
+    }
+
+    // A SpeedEditor that connects to `transport` and authenticates on its first step
+    pub(crate) fn connecting(transport: &MockTransport) -> SpeedEditor {
+        transport.push_auth_handshake();
+        let connector = MockConnector::new();
+        connector.push(transport.clone());
+        crate::with_connector(connector).unwrap()
+    }
+
+    // Poll `done` for up to a second, returns its last result
+    pub(crate) fn wait_until<F: FnMut() -> bool>(mut done: F) -> bool {
+        for _ in 0..500 {
+            if done() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        done()
+    }
+}
@@ -0,0 +1,523 @@
+use serde_json::json;
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use strum::IntoEnumIterator;
+
+use super::{
+    command::Command, JogLed, JogMode, Key, KeyLed, SpeedEditor, SpeedEditorResult, StopToken,
+};
+
+// Connection to a broker
+pub trait MqttClient: Send {
+    fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> SpeedEditorResult;
+
+    fn subscribe(&mut self, filter: &str) -> SpeedEditorResult;
+
+    // Messages received on the subscribed topics since the last call
+    fn poll(&mut self) -> Vec<(String, Vec<u8>)>;
+}
+
+// Whether `topic` matches `filter`, with the + and # wildcards
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(part)) if level == part => {}
+            _ => return false,
+        }
+    }
+    topic.next().is_none()
+}
+
+#[derive(Default, Debug)]
+struct BrokerState {
+    published: Vec<(String, Vec<u8>, bool)>,
+    subscriptions: Vec<String>,
+    incoming: Vec<(String, Vec<u8>)>,
+}
+
+// A broker stand-in keeping everything in memory, clones share the state
+#[derive(Clone, Default, Debug)]
+pub struct LocalBroker {
+    state: Arc<Mutex<BrokerState>>,
+}
+
+impl LocalBroker {
+    pub fn new() -> LocalBroker {
+        LocalBroker::default()
+    }
+
+    // Topic, payload and retain flag of every message published
+    pub fn published(&self) -> Vec<(String, Vec<u8>, bool)> {
+        self.state.lock().unwrap().published.clone()
+    }
+
+    // The last retained payload of `topic`
+    pub fn retained(&self, topic: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state
+            .published
+            .iter()
+            .rev()
+            .find(|(t, _, retain)| t == topic && *retain)
+            .map(|(_, payload, _)| payload.clone())
+    }
+
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.state.lock().unwrap().subscriptions.clone()
+    }
+
+    // Publish from another client, returns false when nobody is subscribed
+    pub fn deliver(&self, topic: &str, payload: &[u8]) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state
+            .subscriptions
+            .iter()
+            .any(|filter| topic_matches(filter, topic))
+        {
+            return false;
+        }
+        state.incoming.push((topic.to_string(), payload.to_vec()));
+        true
+    }
+}
+
+impl MqttClient for LocalBroker {
+    fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> SpeedEditorResult {
+        let mut state = self.state.lock().unwrap();
+        state
+            .published
+            .push((topic.to_string(), payload.to_vec(), retain));
+        Ok(())
+    }
+
+    fn subscribe(&mut self, filter: &str) -> SpeedEditorResult {
+        let mut state = self.state.lock().unwrap();
+        if !state.subscriptions.iter().any(|f| f == filter) {
+            state.subscriptions.push(filter.to_string());
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Vec<(String, Vec<u8>)> {
+        self.state.lock().unwrap().incoming.drain(..).collect()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MqttConfig {
+    // Prefix of every topic, e.g. speededitor/key/Cut
+    pub base_topic: String,
+    // Home Assistant discovery prefix, None to not publish discovery
+    pub discovery_prefix: Option<String>,
+    // Identifies the device in Home Assistant, keep it unique per panel
+    pub device_id: String,
+    pub device_name: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> MqttConfig {
+        MqttConfig {
+            base_topic: "speededitor".to_string(),
+            discovery_prefix: Some("homeassistant".to_string()),
+            device_id: "speededitor".to_string(),
+            device_name: "Speed Editor".to_string(),
+        }
+    }
+}
+
+impl MqttConfig {
+    pub fn new() -> MqttConfig {
+        MqttConfig::default()
+    }
+
+    pub fn base_topic(mut self, topic: &str) -> MqttConfig {
+        self.base_topic = topic.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn discovery_prefix(mut self, prefix: Option<&str>) -> MqttConfig {
+        self.discovery_prefix = prefix.map(|p| p.trim_end_matches('/').to_string());
+        self
+    }
+
+    pub fn device(mut self, id: &str, name: &str) -> MqttConfig {
+        self.device_id = id.to_string();
+        self.device_name = name.to_string();
+        self
+    }
+
+    pub fn topic(&self, suffix: &str) -> String {
+        format!("{}/{}", self.base_topic, suffix)
+    }
+
+    // Retained "online" / "offline"
+    pub fn status_topic(&self) -> String {
+        self.topic("status")
+    }
+
+    // "down" / "up"
+    pub fn key_topic(&self, key: Key) -> String {
+        self.topic(&format!("key/{}", key))
+    }
+
+    // Topic and retained payload of the Home Assistant device trigger of each key
+    pub fn discovery(&self) -> Vec<(String, String)> {
+        let prefix = match &self.discovery_prefix {
+            Some(prefix) => prefix,
+            None => return vec![],
+        };
+        Key::iter()
+            .filter(|key| *key != Key::None)
+            .map(|key| {
+                let topic = format!(
+                    "{}/device_automation/{}/{}_press/config",
+                    prefix, self.device_id, key
+                );
+                let payload = json!({
+                    "automation_type": "trigger",
+                    "topic": self.key_topic(key),
+                    "type": "button_short_press",
+                    "subtype": key.to_string(),
+                    "payload": "down",
+                    "device": {
+                        "identifiers": [self.device_id],
+                        "name": self.device_name,
+                        "manufacturer": "Blackmagic Design",
+                        "model": "DaVinci Resolve Speed Editor",
+                    },
+                });
+                (topic, payload.to_string())
+            })
+            .collect()
+    }
+
+    // The command for a message on a command topic:
+    // led/<KeyLed>/set ON, led/all/set OFF, jogled/<JogLed>/set ON and jogmode/set shuttle
+    pub(crate) fn command(&self, topic: &str, payload: &[u8]) -> Option<Command> {
+        let topic = topic.strip_prefix(&self.base_topic)?.strip_prefix('/')?;
+        let payload = std::str::from_utf8(payload).ok()?.trim();
+        let parts: Vec<&str> = topic.split('/').collect();
+
+        match parts.as_slice() {
+            ["led", "all", "set"] => Some(Command::AllKeyLeds(on(payload)?)),
+            ["led", led, "set"] => Some(Command::KeyLed(KeyLed::from_str(led).ok()?, on(payload)?)),
+            ["jogled", "all", "set"] => Some(Command::AllJogLeds(on(payload)?)),
+            ["jogled", led, "set"] => {
+                Some(Command::JogLed(JogLed::from_str(led).ok()?, on(payload)?))
+            }
+            ["jogmode", "set"] => Some(Command::JogMode(JogMode::from_name(payload)?)),
+            _ => None,
+        }
+    }
+}
+
+fn on(payload: &str) -> Option<bool> {
+    match payload.to_lowercase().as_str() {
+        "on" | "1" | "true" => Some(true),
+        "off" | "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+// Publishes key presses, jog activity, connection state and battery, and
+// applies the LED and jog mode commands received
+pub struct MqttBridge<C: MqttClient> {
+    pub config: MqttConfig,
+    client: Arc<Mutex<C>>,
+}
+
+impl<C: MqttClient + 'static> MqttBridge<C> {
+    const POLL_INTERVAL: u64 = 20;
+
+    pub fn new(config: MqttConfig, client: C) -> MqttBridge<C> {
+        MqttBridge {
+            config,
+            client: Arc::new(Mutex::new(client)),
+        }
+    }
+
+    // Publish the discovery payloads, subscribe to the command topics and add
+    // the publishing handlers to `speed_editor`
+    pub fn attach(&self, speed_editor: &mut SpeedEditor) -> SpeedEditorResult {
+        {
+            let mut client = self.client.lock().unwrap();
+            for (topic, payload) in self.config.discovery() {
+                client.publish(&topic, payload.as_bytes(), true)?;
+            }
+            for suffix in ["led/+/set", "jogled/+/set", "jogmode/set"] {
+                client.subscribe(&self.config.topic(suffix))?;
+            }
+        }
+
+        // A failed publish (e.g. the broker went away) must not stop the panel
+        let (client, topic) = (self.client.clone(), self.config.status_topic());
+        speed_editor.on_connected(move || {
+            let _ = client.lock().unwrap().publish(&topic, b"online", true);
+            Ok(())
+        });
+        let (client, topic) = (self.client.clone(), self.config.status_topic());
+        speed_editor.on_disconnected(move || {
+            let _ = client.lock().unwrap().publish(&topic, b"offline", true);
+            Ok(())
+        });
+        let (client, config) = (self.client.clone(), self.config.clone());
+        speed_editor.on_key(move |key, down| {
+            let payload: &[u8] = if down { b"down" } else { b"up" };
+            let _ = client
+                .lock()
+                .unwrap()
+                .publish(&config.key_topic(key), payload, false);
+            Ok(())
+        });
+        let (client, topic) = (self.client.clone(), self.config.topic("jog"));
+        speed_editor.on_jog(move |mode, value| {
+            let payload = json!({ "mode": mode.to_string(), "value": value }).to_string();
+            let _ = client
+                .lock()
+                .unwrap()
+                .publish(&topic, payload.as_bytes(), false);
+            Ok(())
+        });
+        let (client, topic) = (self.client.clone(), self.config.topic("battery"));
+        speed_editor.on_battery(move |status| {
+            let payload = json!({
+                "level_percent": status.level_percent,
+                "charging": status.charging,
+            })
+            .to_string();
+            let _ = client
+                .lock()
+                .unwrap()
+                .publish(&topic, payload.as_bytes(), true);
+            Ok(())
+        });
+        Ok(())
+    }
+
+    pub fn run(self, speed_editor: SpeedEditor) -> SpeedEditorResult {
+        self.run_until(speed_editor, &StopToken::new())
+    }
+
+    pub fn run_until(self, mut speed_editor: SpeedEditor, token: &StopToken) -> SpeedEditorResult {
+        self.attach(&mut speed_editor)?;
+        let interval = Duration::from_millis(Self::POLL_INTERVAL);
+        let result = speed_editor.run_bridge(
+            token,
+            interval,
+            |_| true,
+            |controller| {
+                let received = self.client.lock().unwrap().poll();
+                let polled = !received.is_empty();
+                // Unknown topics and payloads are ignored
+                for (topic, payload) in received {
+                    if let Some(command) = self.config.command(&topic, &payload) {
+                        controller.send(command)?;
+                    }
+                }
+                Ok(polled)
+            },
+        );
+
+        let _ = self
+            .client
+            .lock()
+            .unwrap()
+            .publish(&self.config.status_topic(), b"offline", true);
+        result
+    }
+}
+
+#[cfg(feature = "rumqttc")]
+pub use self::broker::RumqttcClient;
+
+#[cfg(feature = "rumqttc")]
+mod broker {
+    use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+    use std::{
+        io,
+        sync::mpsc::{channel, Receiver},
+        thread,
+    };
+
+    use super::{MqttClient, MqttConfig, SpeedEditorResult};
+    use crate::SpeedEditorError;
+
+    fn error<E: std::fmt::Display>(e: E) -> SpeedEditorError {
+        io::Error::other(e.to_string()).into()
+    }
+
+    // A broker connection, with the status topic set to "offline" as last will
+    pub struct RumqttcClient {
+        client: Client,
+        incoming: Receiver<(String, Vec<u8>)>,
+    }
+
+    impl RumqttcClient {
+        pub fn connect(config: &MqttConfig, host: &str, port: u16) -> RumqttcClient {
+            let mut options = MqttOptions::new(config.device_id.clone(), host, port);
+            options.set_last_will(LastWill::new(
+                config.status_topic(),
+                "offline",
+                QoS::AtLeastOnce,
+                true,
+            ));
+            let (client, mut connection) = Client::new(options, 64);
+
+            // The connection reconnects on its own while iterated
+            let (tx, incoming) = channel();
+            thread::spawn(move || {
+                for notification in connection.iter() {
+                    if let Ok(Event::Incoming(Packet::Publish(publish))) = notification {
+                        if tx.send((publish.topic, publish.payload.to_vec())).is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+
+            RumqttcClient { client, incoming }
+        }
+    }
+
+    impl MqttClient for RumqttcClient {
+        fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> SpeedEditorResult {
+            self.client
+                .publish(topic, QoS::AtLeastOnce, retain, payload.to_vec())
+                .map_err(error)
+        }
+
+        fn subscribe(&mut self, filter: &str) -> SpeedEditorResult {
+            self.client
+                .subscribe(filter, QoS::AtLeastOnce)
+                .map_err(error)
+        }
+
+        fn poll(&mut self) -> Vec<(String, Vec<u8>)> {
+            self.incoming.try_iter().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{topic_matches, LocalBroker, MqttBridge, MqttConfig};
+    use crate::{
+        speed_editor::{
+            command::Command,
+            mock::fixtures::{connecting, wait_until, CAM1_LIT},
+        },
+        JogMode, KeyLed, MockTransport, StopToken,
+    };
+    use serde_json::Value;
+    use std::thread;
+
+    #[test]
+    fn wildcards() {
+        assert!(topic_matches(
+            "speededitor/led/+/set",
+            "speededitor/led/Cam1/set"
+        ));
+        assert!(topic_matches("speededitor/#", "speededitor/led/Cam1/set"));
+        assert!(!topic_matches(
+            "speededitor/led/+/set",
+            "speededitor/led/Cam1"
+        ));
+        assert!(!topic_matches(
+            "speededitor/led/+",
+            "speededitor/led/Cam1/set"
+        ));
+    }
+
+    #[test]
+    fn commands_from_topics() {
+        let config = MqttConfig::new().base_topic("studio/se");
+
+        assert_eq!(
+            config.command("studio/se/led/Cam1/set", b"ON"),
+            Some(Command::KeyLed(KeyLed::Cam1, true))
+        );
+        assert_eq!(
+            config.command("studio/se/led/all/set", b"0"),
+            Some(Command::AllKeyLeds(false))
+        );
+        assert_eq!(
+            config.command("studio/se/jogmode/set", b"shuttle"),
+            Some(Command::JogMode(JogMode::Absolute))
+        );
+        assert_eq!(config.command("studio/se/led/Nope/set", b"ON"), None);
+        assert_eq!(config.command("other/led/Cam1/set", b"ON"), None);
+    }
+
+    #[test]
+    fn discovery_has_a_trigger_per_key() {
+        let config = MqttConfig::new().device("se1", "Edit bay");
+        let discovery = config.discovery();
+
+        let (topic, payload) = discovery
+            .iter()
+            .find(|(topic, _)| topic.ends_with("/Cut_press/config"))
+            .unwrap();
+        assert_eq!(
+            topic,
+            "homeassistant/device_automation/se1/Cut_press/config"
+        );
+        let payload: Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(payload["topic"], "speededitor/key/Cut");
+        assert_eq!(payload["payload"], "down");
+        assert_eq!(payload["device"]["identifiers"][0], "se1");
+        assert!(!discovery.iter().any(|(topic, _)| topic.contains("/None_")));
+
+        assert!(MqttConfig::new()
+            .discovery_prefix(None)
+            .discovery()
+            .is_empty());
+    }
+
+    #[test]
+    fn bridge_publishes_events_and_drives_leds() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        transport.push_input_report(&[0x4, 0xf, 0x0]);
+        transport.push_input_report(&[0x7, 0x0, 0x50]);
+
+        let broker = LocalBroker::new();
+        let bridge = MqttBridge::new(MqttConfig::new(), broker.clone());
+        let token = StopToken::new();
+        let stop = token.clone();
+        let handle = thread::spawn(move || bridge.run_until(se, &stop));
+
+        wait_until(|| !broker.subscriptions().is_empty());
+        broker.deliver("speededitor/led/Cam1/set", b"ON");
+        let lit = wait_until(|| {
+            transport.written().contains(&CAM1_LIT.to_vec())
+                && broker.retained("speededitor/battery").is_some()
+        });
+
+        token.stop();
+        handle.join().unwrap().unwrap();
+        assert!(lit);
+        assert_eq!(
+            broker.retained("speededitor/battery"),
+            Some(br#"{"charging":false,"level_percent":80}"#.to_vec())
+        );
+        assert!(broker.published().contains(&(
+            "speededitor/key/Cut".to_string(),
+            b"down".to_vec(),
+            false
+        )));
+        assert!(broker
+            .published()
+            .iter()
+            .any(|(topic, payload, _)| topic == "speededitor/status" && payload == b"online"));
+        assert_eq!(
+            broker.retained("speededitor/status"),
+            Some(b"offline".to_vec())
+        );
+    }
+}
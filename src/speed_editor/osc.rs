@@ -0,0 +1,379 @@
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    str::FromStr,
+    time::Duration,
+};
+
+use super::{
+    command::Command, controller::Controller, Event, JogLed, JogMode, Key, KeyLed, SpeedEditor,
+    SpeedEditorError, SpeedEditorResult, StopToken,
+};
+
+// How key and jog values are sent
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OscArg {
+    // 1.0 / 0.0 for keys, the jog value as a float
+    Float,
+    Int,
+    // Keys only, jog values are sent as Int
+    Bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct OscConfig {
+    pub send_to: SocketAddr,
+    // Where LED and jog mode messages are received, None to only send
+    pub listen_on: Option<SocketAddr>,
+    // Per key address and argument type, keys not listed are sent to /<Key>.
+    // A key without its own type uses `key_arg`.
+    pub key_addresses: Vec<(Key, String, Option<OscArg>)>,
+    pub key_arg: OscArg,
+    pub send_key_up: bool,
+    pub jog_address: String,
+    pub jog_arg: OscArg,
+}
+
+impl Default for OscConfig {
+    fn default() -> OscConfig {
+        OscConfig {
+            send_to: SocketAddr::from(([127, 0, 0, 1], 5000)),
+            listen_on: Some(SocketAddr::from(([127, 0, 0, 1], 5001))),
+            key_addresses: vec![],
+            key_arg: OscArg::Float,
+            send_key_up: true,
+            jog_address: "/jog".to_string(),
+            jog_arg: OscArg::Float,
+        }
+    }
+}
+
+impl OscConfig {
+    pub fn new() -> OscConfig {
+        OscConfig::default()
+    }
+
+    pub fn send_to(mut self, addr: SocketAddr) -> OscConfig {
+        self.send_to = addr;
+        self
+    }
+
+    pub fn listen_on(mut self, addr: Option<SocketAddr>) -> OscConfig {
+        self.listen_on = addr;
+        self
+    }
+
+    pub fn key_address(self, key: Key, address: &str) -> OscConfig {
+        self.key_entry(key, address, None)
+    }
+
+    pub fn key_mapping(self, key: Key, address: &str, arg: OscArg) -> OscConfig {
+        self.key_entry(key, address, Some(arg))
+    }
+
+    fn key_entry(mut self, key: Key, address: &str, arg: Option<OscArg>) -> OscConfig {
+        self.key_addresses.retain(|(k, _, _)| *k != key);
+        self.key_addresses.push((key, address.to_string(), arg));
+        self
+    }
+
+    pub fn key_arg(mut self, arg: OscArg) -> OscConfig {
+        self.key_arg = arg;
+        self
+    }
+
+    pub fn send_key_up(mut self, send: bool) -> OscConfig {
+        self.send_key_up = send;
+        self
+    }
+
+    pub fn jog(mut self, address: &str, arg: OscArg) -> OscConfig {
+        self.jog_address = address.to_string();
+        self.jog_arg = arg;
+        self
+    }
+
+    // The message sent for an event, if any
+    pub fn message(&self, event: &Event) -> Option<OscMessage> {
+        let (key, down) = match event {
+            Event::KeyDown(key) => (*key, true),
+            Event::KeyUp(key) if self.send_key_up => (*key, false),
+            Event::Jog(_, value) => {
+                let arg = match self.jog_arg {
+                    OscArg::Float => OscType::Float(*value as f32),
+                    OscArg::Int | OscArg::Bool => OscType::Int(*value),
+                };
+                return Some(OscMessage {
+                    addr: self.jog_address.clone(),
+                    args: vec![arg],
+                });
+            }
+            _ => return None,
+        };
+
+        let (addr, arg) = match self.key_addresses.iter().find(|(k, _, _)| *k == key) {
+            Some((_, addr, arg)) => (addr.clone(), arg.unwrap_or(self.key_arg)),
+            None => (format!("/{}", key), self.key_arg),
+        };
+        let arg = match arg {
+            OscArg::Float => OscType::Float(if down { 1.0 } else { 0.0 }),
+            OscArg::Int => OscType::Int(down as i32),
+            OscArg::Bool => OscType::Bool(down),
+        };
+        Some(OscMessage {
+            addr,
+            args: vec![arg],
+        })
+    }
+}
+
+fn on(arg: Option<&OscType>) -> Option<bool> {
+    match arg? {
+        OscType::Float(v) => Some(*v != 0.0),
+        OscType::Double(v) => Some(*v != 0.0),
+        OscType::Int(v) => Some(*v != 0),
+        OscType::Long(v) => Some(*v != 0),
+        OscType::Bool(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn jog_mode(arg: Option<&OscType>) -> Option<JogMode> {
+    match arg? {
+        OscType::Int(v) => u8::try_from(*v)
+            .ok()
+            .and_then(|mode| JogMode::try_from(mode).ok()),
+        OscType::String(s) => JogMode::from_name(s),
+        _ => None,
+    }
+}
+
+// The command for an incoming message:
+// /led/<KeyLed> 1, /led/all 0, /jogled/<JogLed> 1 and /jogmode shuttle
+pub(crate) fn command(message: &OscMessage) -> Option<Command> {
+    let arg = message.args.first();
+    let mut parts = message.addr.trim_start_matches('/').splitn(2, '/');
+
+    match (parts.next()?, parts.next()) {
+        ("led", Some("all")) => Some(Command::AllKeyLeds(on(arg)?)),
+        ("led", Some(led)) => Some(Command::KeyLed(KeyLed::from_str(led).ok()?, on(arg)?)),
+        ("jogled", Some("all")) => Some(Command::AllJogLeds(on(arg)?)),
+        ("jogled", Some(led)) => Some(Command::JogLed(JogLed::from_str(led).ok()?, on(arg)?)),
+        ("jogmode", None) => Some(Command::JogMode(jog_mode(arg)?)),
+        _ => None,
+    }
+}
+
+fn commands(packet: OscPacket, out: &mut Vec<Command>) {
+    match packet {
+        OscPacket::Message(message) => out.extend(command(&message)),
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                commands(packet, out);
+            }
+        }
+    }
+}
+
+// Sends panel events as OSC and applies the LED and jog mode messages it receives
+pub struct OscBridge {
+    pub config: OscConfig,
+    socket: UdpSocket,
+    listener: Option<UdpSocket>,
+}
+
+impl OscBridge {
+    const LISTEN_TIMEOUT: u64 = 50;
+
+    pub fn new(config: OscConfig) -> Result<OscBridge, SpeedEditorError> {
+        let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))?;
+        let listener = match config.listen_on {
+            Some(addr) => {
+                let listener = UdpSocket::bind(addr)?;
+                listener.set_read_timeout(Some(Duration::from_millis(Self::LISTEN_TIMEOUT)))?;
+                Some(listener)
+            }
+            None => None,
+        };
+
+        Ok(OscBridge {
+            config,
+            socket,
+            listener,
+        })
+    }
+
+    // The address incoming messages are read from, useful with port 0
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref().and_then(|l| l.local_addr().ok())
+    }
+
+    pub fn send(&self, event: &Event) -> SpeedEditorResult {
+        if let Some(message) = self.config.message(event) {
+            let buf = encoder::encode(&OscPacket::Message(message))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+            self.socket.send_to(&buf, self.config.send_to)?;
+        }
+        Ok(())
+    }
+
+    pub fn run(self, speed_editor: SpeedEditor) -> SpeedEditorResult {
+        self.run_until(speed_editor, &StopToken::new())
+    }
+
+    pub fn run_until(self, speed_editor: SpeedEditor, token: &StopToken) -> SpeedEditorResult {
+        let socket = self.socket.try_clone()?;
+        let config = self.config.clone();
+        let sender = OscBridge {
+            config,
+            socket,
+            listener: None,
+        };
+        let on_event = move |event| {
+            let _ = sender.send(&event);
+            true
+        };
+
+        // The read timeout paces the loop while listening
+        let interval = Duration::from_millis(Self::LISTEN_TIMEOUT);
+        speed_editor.run_bridge(token, interval, on_event, |controller| {
+            match &self.listener {
+                Some(listener) => self.receive(listener, controller).map(|_| true),
+                None => Ok(false),
+            }
+        })
+    }
+
+    fn receive(&self, listener: &UdpSocket, controller: &Controller) -> SpeedEditorResult {
+        let mut buf = [0; 1536];
+        let len = match listener.recv_from(&mut buf) {
+            Ok((len, _)) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(())
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // Malformed packets and unknown addresses are ignored
+        if let Ok((_, packet)) = decoder::decode_udp(&buf[..len]) {
+            let mut out = vec![];
+            commands(packet, &mut out);
+            for command in out {
+                controller.send(command)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command, OscArg, OscBridge, OscConfig};
+    use crate::{
+        speed_editor::{
+            command::Command,
+            mock::fixtures::{connecting, wait_until, CAM1_LIT},
+        },
+        Event, JogMode, Key, KeyLed, MockTransport, StopToken,
+    };
+    use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+    use std::{net::UdpSocket, thread, time::Duration};
+
+    fn message(addr: &str, arg: OscType) -> OscMessage {
+        OscMessage {
+            addr: addr.to_string(),
+            args: vec![arg],
+        }
+    }
+
+    #[test]
+    fn events_are_mapped_to_messages() {
+        let config = OscConfig::new()
+            .key_address(Key::Cut, "/edit/cut")
+            .key_mapping(Key::Esc, "/edit/undo", OscArg::Bool)
+            .key_arg(OscArg::Int)
+            .jog("/wheel", OscArg::Float);
+
+        assert_eq!(
+            config.message(&Event::KeyDown(Key::Cut)),
+            Some(message("/edit/cut", OscType::Int(1)))
+        );
+        assert_eq!(
+            config.message(&Event::KeyDown(Key::Esc)),
+            Some(message("/edit/undo", OscType::Bool(true)))
+        );
+        assert_eq!(
+            config.message(&Event::KeyUp(Key::In)),
+            Some(message("/In", OscType::Int(0)))
+        );
+        assert_eq!(
+            config.message(&Event::Jog(JogMode::Relative, -2)),
+            Some(message("/wheel", OscType::Float(-2.0)))
+        );
+        assert_eq!(config.message(&Event::Connected), None);
+    }
+
+    #[test]
+    fn messages_are_mapped_to_commands() {
+        assert_eq!(
+            command(&message("/led/Cam1", OscType::Int(1))),
+            Some(Command::KeyLed(KeyLed::Cam1, true))
+        );
+        assert_eq!(
+            command(&message("/led/all", OscType::Float(0.0))),
+            Some(Command::AllKeyLeds(false))
+        );
+        assert_eq!(
+            command(&message("/jogmode", OscType::String("shuttle".to_string()))),
+            Some(Command::JogMode(JogMode::Absolute))
+        );
+        assert_eq!(
+            command(&message("/jogmode", OscType::Int(3))),
+            Some(Command::JogMode(JogMode::AbsoluteDeadzone))
+        );
+        assert_eq!(command(&message("/jogmode", OscType::Int(259))), None);
+        assert_eq!(command(&message("/led/Nope", OscType::Int(1))), None);
+        assert_eq!(command(&message("/unknown", OscType::Int(1))), None);
+    }
+
+    #[test]
+    fn bridge_sends_events_and_drives_leds() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+        transport.push_input_report(&[0x4, 0xf, 0x0]);
+
+        let host = UdpSocket::bind("127.0.0.1:0").unwrap();
+        host.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let config = OscConfig::new()
+            .send_to(host.local_addr().unwrap())
+            .listen_on(Some("127.0.0.1:0".parse().unwrap()));
+        let bridge = OscBridge::new(config).unwrap();
+        let bridge_addr = bridge.local_addr().unwrap();
+
+        let token = StopToken::new();
+        let stop = token.clone();
+        let handle = thread::spawn(move || bridge.run_until(se, &stop));
+
+        let mut buf = [0; 1536];
+        let (len, _) = host.recv_from(&mut buf).unwrap();
+        let (_, packet) = decoder::decode_udp(&buf[..len]).unwrap();
+        assert_eq!(
+            packet,
+            OscPacket::Message(message("/Cut", OscType::Float(1.0)))
+        );
+
+        let led = encoder::encode(&OscPacket::Message(message("/led/Cam1", OscType::Int(1))));
+        host.send_to(&led.unwrap(), bridge_addr).unwrap();
+        let lit = wait_until(|| transport.written().contains(&CAM1_LIT.to_vec()));
+
+        token.stop();
+        handle.join().unwrap().unwrap();
+        assert!(lit);
+    }
+}
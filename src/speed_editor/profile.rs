@@ -0,0 +1,721 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::VecDeque, path::Path, str::FromStr, time::Duration};
+
+use super::{
+    jog::AccelCurve,
+    layer::{Layer, LayerStack, LayerSwitch, BASE_LAYER},
+    Event, Events, Gesture, GestureConfig, JogConfig, JogMode, JogTracker, Key, KeyLed, LedEffect,
+    SpeedEditor, SpeedEditorError, SpeedEditorResult,
+};
+
+// Keymap file as written by the user, see Keymap for the checked version
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub name: Option<String>,
+    // Keys only used in chords, see ChordRegistry
+    pub modifiers: Vec<String>,
+    pub layers: Vec<LayerProfile>,
+    pub bindings: Vec<Binding>,
+    pub leds: Vec<LedRule>,
+    pub jog: Option<JogProfile>,
+}
+
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayerProfile {
+    pub name: String,
+    // The key switching to the layer
+    pub key: Option<String>,
+    // momentary (default) or toggle
+    pub switch: Option<String>,
+    pub leds: Vec<String>,
+}
+
+// Exactly one of key, chord or step. A gesture applies to the key.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Binding {
+    // Bindings without a layer belong to the base layer
+    pub layer: Option<String>,
+    pub key: Option<String>,
+    pub chord: Option<Vec<String>>,
+    // tap, double_tap, long_press or held
+    pub gesture: Option<String>,
+    // 1 or -1, needs jog.ticks_per_step
+    pub step: Option<i32>,
+    // Fire the chord when released instead of pressed
+    pub on_release: bool,
+    pub action: String,
+    pub params: Map<String, Value>,
+}
+
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LedRule {
+    pub leds: Vec<String>,
+    // on, blink, pulse or chase
+    pub effect: Option<String>,
+    pub period_ms: Option<u64>,
+}
+
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JogProfile {
+    pub mode: Option<String>,
+    pub leds_follow_mode: bool,
+    pub ticks_per_step: Option<i64>,
+    pub acceleration: Option<f64>,
+    pub shuttle_speeds: Option<Vec<f32>>,
+}
+
+impl Profile {
+    pub fn from_toml(s: &str) -> Result<Profile, SpeedEditorError> {
+        toml::from_str(s).map_err(|e| SpeedEditorError::ProfileError(e.to_string()))
+    }
+
+    pub fn from_json(s: &str) -> Result<Profile, SpeedEditorError> {
+        serde_json::from_str(s).map_err(|e| SpeedEditorError::ProfileError(e.to_string()))
+    }
+
+    // JSON for a .json file, TOML otherwise
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Profile, SpeedEditorError> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Profile::from_json(&s),
+            _ => Profile::from_toml(&s),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Trigger {
+    Key(Key),
+    Chord(Vec<Key>),
+    Tap(Key),
+    DoubleTap(Key),
+    LongPress(Key),
+    Held(Key),
+    Step(i32),
+}
+
+impl Trigger {
+    fn matches(&self, event: &Event) -> bool {
+        match (self, event) {
+            (Trigger::Key(k), Event::KeyDown(key)) => k == key,
+            (Trigger::Chord(keys), Event::Chord(chord)) => {
+                keys.len() == chord.len() && keys.iter().all(|k| chord.contains(k))
+            }
+            (Trigger::Tap(k), Event::Gesture(Gesture::Tap(key)))
+            | (Trigger::DoubleTap(k), Event::Gesture(Gesture::DoubleTap(key)))
+            | (Trigger::LongPress(k), Event::Gesture(Gesture::LongPress(key, _)))
+            | (Trigger::Held(k), Event::Gesture(Gesture::Held(key, _))) => k == key,
+            (Trigger::Step(d), Event::Step(direction)) => d == direction,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Action {
+    pub name: String,
+    pub params: Map<String, Value>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyBinding {
+    pub layer: String,
+    pub trigger: Trigger,
+    pub action: Action,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct LayerSetting {
+    layer: Layer,
+    switch: Option<(Key, LayerSwitch)>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum LedSetting {
+    On(Vec<KeyLed>),
+    Effect(LedEffect),
+}
+
+// A validated Profile, ready to be applied to a SpeedEditor
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Keymap {
+    pub bindings: Vec<KeyBinding>,
+    modifiers: Vec<Key>,
+    layers: Vec<LayerSetting>,
+    chords: Vec<(Vec<Key>, bool)>,
+    leds: Vec<LedSetting>,
+    jog: Option<JogProfile>,
+    jog_mode: Option<JogMode>,
+}
+
+fn parse<T: FromStr>(what: &str, at: &str, name: &str) -> Result<T, SpeedEditorError> {
+    T::from_str(name)
+        .map_err(|_| SpeedEditorError::ProfileError(format!("{}: unknown {} `{}`", at, what, name)))
+}
+
+fn invalid(at: &str, msg: &str) -> SpeedEditorError {
+    SpeedEditorError::ProfileError(format!("{}: {}", at, msg))
+}
+
+impl Keymap {
+    // Errors name the offending entry, e.g. `bindings[2].key: unknown key `Cutt``
+    pub fn from_profile(profile: &Profile) -> Result<Keymap, SpeedEditorError> {
+        let mut keymap = Keymap::default();
+
+        for (i, name) in profile.modifiers.iter().enumerate() {
+            let key = parse("key", &format!("modifiers[{}]", i), name)?;
+            keymap.modifiers.push(key);
+        }
+
+        for (i, layer) in profile.layers.iter().enumerate() {
+            let at = format!("layers[{}]", i);
+            if layer.name.is_empty() || layer.name == BASE_LAYER {
+                return Err(invalid(
+                    &format!("{}.name", at),
+                    "needs a name other than base",
+                ));
+            }
+            if keymap.layers.iter().any(|l| l.layer.name == layer.name) {
+                return Err(invalid(
+                    &format!("{}.name", at),
+                    &format!("duplicate layer `{}`", layer.name),
+                ));
+            }
+            let switch = match layer.switch.as_deref().unwrap_or("momentary") {
+                "momentary" => LayerSwitch::Momentary,
+                "toggle" => LayerSwitch::Toggle,
+                switch => {
+                    return Err(invalid(
+                        &format!("{}.switch", at),
+                        &format!("unknown switch `{}`", switch),
+                    ))
+                }
+            };
+            let key = match &layer.key {
+                Some(name) => Some((parse("key", &format!("{}.key", at), name)?, switch)),
+                None => None,
+            };
+            let leds = layer
+                .leds
+                .iter()
+                .enumerate()
+                .map(|(j, led)| parse("led", &format!("{}.leds[{}]", at, j), led))
+                .collect::<Result<Vec<KeyLed>, _>>()?;
+            keymap.layers.push(LayerSetting {
+                layer: Layer {
+                    name: layer.name.clone(),
+                    leds,
+                },
+                switch: key,
+            });
+        }
+
+        for (i, binding) in profile.bindings.iter().enumerate() {
+            let at = format!("bindings[{}]", i);
+            let layer = binding.layer.as_deref().unwrap_or(BASE_LAYER);
+            if layer != BASE_LAYER && !keymap.layers.iter().any(|l| l.layer.name == layer) {
+                return Err(invalid(
+                    &format!("{}.layer", at),
+                    &format!("unknown layer `{}`", layer),
+                ));
+            }
+            let trigger = keymap.trigger(binding, &at)?;
+            let key = match trigger {
+                Trigger::Key(key)
+                | Trigger::Tap(key)
+                | Trigger::DoubleTap(key)
+                | Trigger::LongPress(key)
+                | Trigger::Held(key) => Some(key),
+                _ => None,
+            };
+            let switching = key.and_then(|key| {
+                keymap
+                    .layers
+                    .iter()
+                    .find(|l| matches!(l.switch, Some((k, _)) if k == key))
+            });
+            if let Some(switch) = switching {
+                return Err(invalid(
+                    &format!("{}.key", at),
+                    &format!("already switches layer `{}`", switch.layer.name),
+                ));
+            }
+            if binding.action.is_empty() {
+                return Err(invalid(&format!("{}.action", at), "missing action"));
+            }
+            let action = Action {
+                name: binding.action.clone(),
+                params: binding.params.clone(),
+            };
+            keymap.bindings.push(KeyBinding {
+                layer: layer.to_string(),
+                trigger,
+                action,
+            });
+        }
+
+        for (i, rule) in profile.leds.iter().enumerate() {
+            let at = format!("leds[{}]", i);
+            let leds = rule
+                .leds
+                .iter()
+                .enumerate()
+                .map(|(j, led)| parse("led", &format!("{}.leds[{}]", at, j), led))
+                .collect::<Result<Vec<KeyLed>, _>>()?;
+            if rule.period_ms == Some(0) {
+                return Err(invalid(&format!("{}.period_ms", at), "must be positive"));
+            }
+            let period = Duration::from_millis(rule.period_ms.unwrap_or(500));
+            let setting = match rule.effect.as_deref().unwrap_or("on") {
+                "on" => LedSetting::On(leds),
+                "blink" => LedSetting::Effect(LedEffect::blink(leds, period)),
+                "pulse" => LedSetting::Effect(LedEffect::pulse(leds, period)),
+                "chase" => LedSetting::Effect(LedEffect::chase(leds, period)),
+                effect => {
+                    return Err(invalid(
+                        &format!("{}.effect", at),
+                        &format!("unknown effect `{}`", effect),
+                    ))
+                }
+            };
+            keymap.leds.push(setting);
+        }
+
+        if let Some(jog) = &profile.jog {
+            if let Some(mode) = &jog.mode {
+                keymap.jog_mode =
+                    Some(JogMode::from_name(mode).ok_or_else(|| {
+                        invalid("jog.mode", &format!("unknown jog mode `{}`", mode))
+                    })?);
+            }
+            if matches!(jog.ticks_per_step, Some(ticks) if ticks <= 0) {
+                return Err(invalid("jog.ticks_per_step", "must be positive"));
+            }
+            keymap.jog = Some(jog.clone());
+        }
+
+        let steps = keymap
+            .bindings
+            .iter()
+            .any(|b| matches!(b.trigger, Trigger::Step(_)));
+        let ticks = profile.jog.as_ref().and_then(|j| j.ticks_per_step);
+        if steps && ticks.is_none() {
+            return Err(invalid("jog.ticks_per_step", "needed by the step bindings"));
+        }
+
+        Ok(keymap)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Keymap, SpeedEditorError> {
+        Keymap::from_profile(&Profile::load(path)?)
+    }
+
+    fn trigger(&mut self, binding: &Binding, at: &str) -> Result<Trigger, SpeedEditorError> {
+        let count = [
+            binding.key.is_some(),
+            binding.chord.is_some(),
+            binding.step.is_some(),
+        ]
+        .iter()
+        .filter(|&&set| set)
+        .count();
+        if count != 1 {
+            return Err(invalid(at, "needs exactly one of key, chord or step"));
+        }
+        if binding.gesture.is_some() && binding.key.is_none() {
+            return Err(invalid(&format!("{}.gesture", at), "needs a key"));
+        }
+
+        if let Some(name) = &binding.key {
+            let key = parse("key", &format!("{}.key", at), name)?;
+            return match binding.gesture.as_deref() {
+                None => Ok(Trigger::Key(key)),
+                Some("tap") => Ok(Trigger::Tap(key)),
+                Some("double_tap") => Ok(Trigger::DoubleTap(key)),
+                Some("long_press") => Ok(Trigger::LongPress(key)),
+                Some("held") => Ok(Trigger::Held(key)),
+                Some(gesture) => Err(invalid(
+                    &format!("{}.gesture", at),
+                    &format!("unknown gesture `{}`", gesture),
+                )),
+            };
+        }
+
+        if let Some(names) = &binding.chord {
+            if names.len() < 2 {
+                return Err(invalid(&format!("{}.chord", at), "needs at least two keys"));
+            }
+            let keys = names
+                .iter()
+                .enumerate()
+                .map(|(j, name)| parse("key", &format!("{}.chord[{}]", at, j), name))
+                .collect::<Result<Vec<Key>, _>>()?;
+            self.chords.push((keys.clone(), binding.on_release));
+            return Ok(Trigger::Chord(keys));
+        }
+
+        match binding.step {
+            Some(direction) if direction == 1 || direction == -1 => Ok(Trigger::Step(direction)),
+            _ => Err(invalid(&format!("{}.step", at), "must be 1 or -1")),
+        }
+    }
+
+    // Register the chords, gestures, LEDs and jog settings the keymap needs.
+    // The chords, layers, LED effects and lit key and jog LEDs of before are
+    // replaced, so applying a keymap does not depend on what was applied earlier.
+    pub fn apply(&self, speed_editor: &mut SpeedEditor) -> SpeedEditorResult {
+        speed_editor.chords.clear();
+        speed_editor.layers = LayerStack::new();
+        speed_editor.clear_led_effects()?;
+        speed_editor.set_all_key_leds(false)?;
+        speed_editor.set_all_jog_leds(false)?;
+
+        for key in self.modifiers.iter() {
+            speed_editor.chords.add_modifier(*key);
+        }
+        for (keys, on_release) in self.chords.iter() {
+            if *on_release {
+                speed_editor.chords.add_on_release(keys);
+            } else {
+                speed_editor.chords.add(keys);
+            }
+        }
+
+        for setting in self.layers.iter() {
+            let layer = &setting.layer;
+            speed_editor
+                .layers
+                .add_layer(&layer.name, layer.leds.clone());
+            if let Some((key, switch)) = setting.switch {
+                speed_editor.layers.add_switch(key, &layer.name, switch);
+            }
+        }
+
+        let gestures = self.bindings.iter().any(|b| {
+            matches!(
+                b.trigger,
+                Trigger::Tap(_) | Trigger::DoubleTap(_) | Trigger::LongPress(_) | Trigger::Held(_)
+            )
+        });
+        if gestures && speed_editor.gestures.is_none() {
+            speed_editor.enable_gestures(GestureConfig::new());
+        }
+
+        for setting in self.leds.iter() {
+            match setting {
+                LedSetting::On(leds) => speed_editor.set_leds(leds.clone(), true)?,
+                LedSetting::Effect(effect) => {
+                    speed_editor.add_led_effect(effect.clone())?;
+                }
+            }
+        }
+
+        if let Some(jog) = &self.jog {
+            if let Some(mode) = self.jog_mode {
+                speed_editor.set_jog_mode(mode)?;
+            }
+            speed_editor.set_jog_leds_follow_mode(jog.leds_follow_mode)?;
+            if let Some(ticks) = jog.ticks_per_step {
+                speed_editor.enable_jog_tracking(JogTracker::new(ticks));
+            }
+            if jog.acceleration.is_some() || jog.shuttle_speeds.is_some() {
+                let mut config = JogConfig::new();
+                if let Some(factor) = jog.acceleration {
+                    config = config.curve(AccelCurve::Linear { factor });
+                }
+                if let Some(speeds) = &jog.shuttle_speeds {
+                    config = config.shuttle_speeds(speeds);
+                }
+                speed_editor.enable_jog_processing(config);
+            }
+        }
+        Ok(())
+    }
+
+    // The actions bound to an event on the base layer
+    pub fn actions_for(&self, event: &Event) -> Vec<Action> {
+        self.actions_in(event, BASE_LAYER)
+    }
+
+    // The actions bound to an event on `layer`, falling back to the base layer
+    pub fn actions_in(&self, event: &Event, layer: &str) -> Vec<Action> {
+        let find = |layer: &str| -> Vec<Action> {
+            self.bindings
+                .iter()
+                .filter(|b| b.layer == layer && b.trigger.matches(event))
+                .map(|b| b.action.clone())
+                .collect()
+        };
+
+        let actions = find(layer);
+        if actions.is_empty() && layer != BASE_LAYER {
+            find(BASE_LAYER)
+        } else {
+            actions
+        }
+    }
+}
+
+impl SpeedEditor {
+    // Apply `keymap` and turn the events into its actions
+    pub fn actions<'a>(&'a mut self, keymap: &'a Keymap) -> Result<Actions<'a>, SpeedEditorError> {
+        keymap.apply(self)?;
+        let layer = self.layers.active().name.clone();
+        Ok(Actions {
+            events: self.events(),
+            keymap,
+            layer,
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+pub struct Actions<'a> {
+    events: Events<'a>,
+    keymap: &'a Keymap,
+    // Followed through the LayerChanged events
+    layer: String,
+    pending: VecDeque<Action>,
+}
+
+impl Iterator for Actions<'_> {
+    type Item = Result<Action, SpeedEditorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(action) = self.pending.pop_front() {
+                return Some(Ok(action));
+            }
+            match self.events.next()? {
+                Ok(Event::LayerChanged(layer)) => self.layer = layer,
+                Ok(event) => self
+                    .pending
+                    .extend(self.keymap.actions_in(&event, &self.layer)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Keymap, Profile, Trigger};
+    use crate::{
+        speed_editor::mock::fixtures::authenticated, Event, Gesture, JogLed, JogMode, Key, KeyLed,
+        MockTransport, SpeedEditorError,
+    };
+    use serde_json::json;
+
+    const PROFILE: &str = r#"
+name = "editing"
+modifiers = ["Shtl"]
+
+[[bindings]]
+key = "Cut"
+action = "razor"
+
+[[bindings]]
+chord = ["Shtl", "Cut"]
+action = "ripple_delete"
+
+[[bindings]]
+key = "Esc"
+gesture = "double_tap"
+action = "undo"
+params = { count = 2 }
+
+[[bindings]]
+step = 1
+action = "next_frame"
+
+[[leds]]
+leds = ["Cam1"]
+effect = "blink"
+period_ms = 400
+
+[jog]
+mode = "shuttle"
+ticks_per_step = 360
+"#;
+
+    #[test]
+    fn toml_profile_is_compiled() {
+        let keymap = Keymap::from_profile(&Profile::from_toml(PROFILE).unwrap()).unwrap();
+
+        assert_eq!(keymap.bindings.len(), 4);
+        assert_eq!(
+            keymap.bindings[1].trigger,
+            Trigger::Chord(vec![Key::Shtl, Key::Cut])
+        );
+        assert_eq!(
+            keymap.actions_for(&Event::Gesture(Gesture::DoubleTap(Key::Esc))),
+            vec![Action {
+                name: "undo".to_string(),
+                params: json!({ "count": 2 }).as_object().unwrap().clone(),
+            }]
+        );
+        assert_eq!(keymap.actions_for(&Event::Step(1))[0].name, "next_frame");
+        assert!(keymap.actions_for(&Event::KeyUp(Key::Cut)).is_empty());
+        assert_eq!(keymap.jog_mode, Some(JogMode::Absolute));
+    }
+
+    #[test]
+    fn applying_twice_registers_once() {
+        let keymap = Keymap::from_profile(&Profile::from_toml(PROFILE).unwrap()).unwrap();
+        let mut se = crate::with_connector(crate::MockConnector::new()).unwrap();
+        se.chords.add(&[Key::In, Key::Out]);
+
+        keymap.apply(&mut se).unwrap();
+        keymap.apply(&mut se).unwrap();
+        assert_eq!(se.chords.chords().len(), 1);
+        assert_eq!(se.chords.modifiers(), &[Key::Shtl]);
+        assert_eq!(se.layers.layers().len(), 1);
+    }
+
+    #[test]
+    fn applying_clears_leds_of_the_previous_keymap() {
+        let keymap = |s: &str| Keymap::from_profile(&Profile::from_toml(s).unwrap()).unwrap();
+        let mut se = crate::with_connector(crate::MockConnector::new()).unwrap();
+        se.set_jog_led(JogLed::Jog, true).unwrap();
+
+        keymap("[[leds]]\nleds = [\"Cam1\"]\n")
+            .apply(&mut se)
+            .unwrap();
+        keymap("[[leds]]\nleds = [\"Cam2\"]\n")
+            .apply(&mut se)
+            .unwrap();
+        assert_eq!(se.current_key_leds, vec![KeyLed::Cam2]);
+        assert!(se.current_jog_leds.is_empty());
+    }
+
+    #[test]
+    fn example_profile_loads() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/profiles/editing.toml"
+        );
+        let keymap = Keymap::load(path).unwrap();
+
+        assert_eq!(
+            keymap.actions_for(&Event::Step(-1))[0].name,
+            "previous_frame"
+        );
+    }
+
+    #[test]
+    fn json_profile_is_accepted() {
+        let profile =
+            Profile::from_json(r#"{ "bindings": [{ "key": "In", "action": "mark_in" }] }"#)
+                .unwrap();
+        let keymap = Keymap::from_profile(&profile).unwrap();
+
+        assert_eq!(
+            keymap.actions_for(&Event::KeyDown(Key::In))[0].name,
+            "mark_in"
+        );
+    }
+
+    #[test]
+    fn errors_point_to_the_entry() {
+        let error = |s: &str| match Keymap::from_profile(&Profile::from_toml(s).unwrap()) {
+            Err(SpeedEditorError::ProfileError(msg)) => msg,
+            r => panic!("unexpected {:?}", r),
+        };
+
+        assert_eq!(
+            error("[[bindings]]\nkey = \"In\"\naction = \"a\"\n[[bindings]]\nkey = \"Cutt\"\naction = \"b\"\n"),
+            "bindings[1].key: unknown key `Cutt`"
+        );
+        assert_eq!(
+            error("[[bindings]]\nchord = [\"Shtl\", \"Nope\"]\naction = \"a\"\n"),
+            "bindings[0].chord[1]: unknown key `Nope`"
+        );
+        assert_eq!(
+            error("[[leds]]\nleds = [\"Cam1\"]\neffect = \"strobe\"\n"),
+            "leds[0].effect: unknown effect `strobe`"
+        );
+        assert!(Profile::from_toml("[[bindings]]\nkeys = \"In\"\n").is_err());
+        assert_eq!(
+            error("[[bindings]]\nlayer = \"trim\"\nkey = \"In\"\naction = \"a\"\n"),
+            "bindings[0].layer: unknown layer `trim`"
+        );
+        assert_eq!(
+            error("[[leds]]\nleds = [\"Cam1\"]\neffect = \"chase\"\nperiod_ms = 0\n"),
+            "leds[0].period_ms: must be positive"
+        );
+        assert_eq!(
+            error("[[layers]]\nname = \"color\"\n[[layers]]\nname = \"color\"\n"),
+            "layers[1].name: duplicate layer `color`"
+        );
+        assert_eq!(
+            error("[[layers]]\nname = \"color\"\nkey = \"Source\"\n[[bindings]]\nkey = \"Source\"\ngesture = \"tap\"\naction = \"a\"\n"),
+            "bindings[0].key: already switches layer `color`"
+        );
+    }
+
+    #[test]
+    fn actions_are_read_from_the_panel() {
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x4, 0x1c, 0x0]);
+        transport.push_input_report(&[0x4, 0x1c, 0x0, 0xf, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0]);
+        transport.push_input_report(&[0x4, 0xf, 0x0]);
+
+        let mut se = authenticated(&transport);
+
+        let keymap = Keymap::from_profile(&Profile::from_toml(PROFILE).unwrap()).unwrap();
+        let actions: Vec<String> = se
+            .actions(&keymap)
+            .unwrap()
+            .take(2)
+            .map(|a| a.unwrap().name)
+            .collect();
+        assert_eq!(actions, vec!["ripple_delete", "razor"]);
+    }
+
+    #[test]
+    fn layer_bindings_replace_base_bindings() {
+        let profile = Profile::from_toml(
+            r#"
+[[layers]]
+name = "color"
+key = "Source"
+leds = ["Cam1"]
+
+[[bindings]]
+key = "Cut"
+action = "razor"
+
+[[bindings]]
+layer = "color"
+key = "Cut"
+action = "next_node"
+"#,
+        )
+        .unwrap();
+        let keymap = Keymap::from_profile(&profile).unwrap();
+
+        let transport = MockTransport::new();
+        transport.push_input_report(&[0x4, 0x1a, 0x0]);
+        transport.push_input_report(&[0x4, 0x1a, 0x0, 0xf, 0x0]);
+        transport.push_input_report(&[0x4, 0x0, 0x0]);
+        transport.push_input_report(&[0x4, 0xf, 0x0]);
+
+        let mut se = authenticated(&transport);
+
+        let actions: Vec<String> = se
+            .actions(&keymap)
+            .unwrap()
+            .take(2)
+            .map(|a| a.unwrap().name)
+            .collect();
+        assert_eq!(actions, vec!["next_node", "razor"]);
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(feature = "hidapi")]
+use super::device_info::DeviceInfo;
+use super::SpeedEditorError;
+
+// Raw report I/O used by SpeedEditor. The signatures follow hidapi::HidDevice.
+pub trait Transport: Send {
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, SpeedEditorError>;
+    fn write(&self, data: &[u8]) -> Result<usize, SpeedEditorError>;
+    fn send_feature_report(&self, data: &[u8]) -> Result<(), SpeedEditorError>;
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize, SpeedEditorError>;
+}
+
+// Opens a transport to the panel.
+// Ok(None) means no panel is available yet and the caller should try again later.
+pub trait Connector: Send {
+    fn open(&mut self) -> Result<Option<Box<dyn Transport>>, SpeedEditorError>;
+}
+
+#[cfg(feature = "hidapi")]
+impl Transport for hidapi::HidDevice {
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, SpeedEditorError> {
+        Ok(hidapi::HidDevice::read_timeout(self, buf, timeout)?)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<usize, SpeedEditorError> {
+        Ok(hidapi::HidDevice::write(self, data)?)
+    }
+
+    fn send_feature_report(&self, data: &[u8]) -> Result<(), SpeedEditorError> {
+        Ok(hidapi::HidDevice::send_feature_report(self, data)?)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> Result<usize, SpeedEditorError> {
+        Ok(hidapi::HidDevice::get_feature_report(self, buf)?)
+    }
+}
+
+#[cfg(feature = "hidapi")]
+const VID: u16 = 7899;
+#[cfg(feature = "hidapi")]
+const PID: u16 = 55822;
+
+// All connected Speed Editors
+#[cfg(feature = "hidapi")]
+pub fn list_devices() -> Result<Vec<DeviceInfo>, SpeedEditorError> {
+    let api = hidapi::HidApi::new()?;
+
+    Ok(api
+        .device_list()
+        .filter(|d| d.vendor_id() == VID && d.product_id() == PID)
+        .map(|d| DeviceInfo {
+            path: d.path().to_string_lossy().into_owned(),
+            serial_number: d.serial_number().map(String::from),
+            interface_number: d.interface_number(),
+            product: d.product_string().map(String::from),
+            release_number: d.release_number(),
+        })
+        .collect())
+}
+
+#[cfg(feature = "hidapi")]
+#[derive(Clone, Default, Debug)]
+enum DeviceSelector {
+    #[default]
+    Any,
+    Serial(String),
+    Path(String),
+}
+
+#[cfg(feature = "hidapi")]
+#[derive(Default)]
+pub struct HidConnector {
+    selector: DeviceSelector,
+}
+
+#[cfg(feature = "hidapi")]
+impl HidConnector {
+    // Opens the first Speed Editor found
+    pub fn new() -> HidConnector {
+        HidConnector::default()
+    }
+
+    pub fn serial(serial: &str) -> HidConnector {
+        HidConnector {
+            selector: DeviceSelector::Serial(serial.to_string()),
+        }
+    }
+
+    pub fn path(path: &str) -> HidConnector {
+        HidConnector {
+            selector: DeviceSelector::Path(path.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "hidapi")]
+impl Connector for HidConnector {
+    fn open(&mut self) -> Result<Option<Box<dyn Transport>>, SpeedEditorError> {
+        let api = hidapi::HidApi::new()?;
+
+        let device = match &self.selector {
+            DeviceSelector::Any => api.open(VID, PID),
+            DeviceSelector::Serial(serial) => api.open_serial(VID, PID, serial),
+            DeviceSelector::Path(path) => {
+                let path = std::ffi::CString::new(path.as_str())
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+                api.open_path(&path)
+            }
+        };
+
+        match device {
+            Ok(device) => Ok(Some(Box::new(device))),
+            Err(_) => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,424 @@
+use serde_json::{json, Value};
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    str::FromStr,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tungstenite::{Message, WebSocket};
+
+use super::{
+    command::Command, controller::Controller, controller::SpeedEditorState, Event, Gesture, JogLed,
+    JogMode, KeyLed, SpeedEditor, SpeedEditorError, SpeedEditorResult, StopToken,
+};
+
+// The JSON message sent for an event, `type` names the event:
+// {"type": "key_down", "key": "Cut"}, {"type": "jog", "mode": "Relative", "value": -1}.
+// As in Event::Jog, relative jog values are in steps (raw / 360); the jog_motion
+// `raw` is in raw units.
+pub fn event_json(event: &Event) -> Value {
+    match event {
+        Event::Connected => json!({ "type": "connected" }),
+        Event::Disconnected => json!({ "type": "disconnected" }),
+        Event::KeyDown(key) => json!({ "type": "key_down", "key": key.to_string() }),
+        Event::KeyUp(key) => json!({ "type": "key_up", "key": key.to_string() }),
+        Event::UnknownKey(code, down) => {
+            json!({ "type": "unknown_key", "code": code, "down": down })
+        }
+        Event::Keys(keys) => json!({ "type": "keys", "keys": names(keys) }),
+        Event::Chord(keys) => json!({ "type": "chord", "keys": names(keys) }),
+        Event::LayerChanged(layer) => json!({ "type": "layer_changed", "layer": layer }),
+        Event::Gesture(gesture) => match gesture {
+            Gesture::Tap(key) => json!({ "type": "tap", "key": key.to_string() }),
+            Gesture::DoubleTap(key) => json!({ "type": "double_tap", "key": key.to_string() }),
+            Gesture::LongPress(key, held) => json!({
+                "type": "long_press",
+                "key": key.to_string(),
+                "held_ms": held.as_millis() as u64,
+            }),
+            Gesture::Held(key, count) => {
+                json!({ "type": "held", "key": key.to_string(), "count": count })
+            }
+        },
+        Event::Jog(mode, value) => {
+            json!({ "type": "jog", "mode": mode.to_string(), "value": value })
+        }
+        Event::JogMotion(motion) => json!({
+            "type": "jog_motion",
+            "mode": motion.mode.to_string(),
+            "raw": motion.raw,
+            "delta": motion.delta,
+            "velocity": motion.velocity,
+        }),
+        Event::ShuttleSpeed(speed) => json!({ "type": "shuttle_speed", "speed": speed }),
+        Event::Step(steps) => json!({ "type": "step", "steps": steps }),
+        Event::Battery(status) => json!({
+            "type": "battery",
+            "level_percent": status.level_percent,
+            "charging": status.charging,
+        }),
+        Event::Unknown(report) => json!({ "type": "unknown", "report": report }),
+    }
+}
+
+// Sent to each client once connected, battery is null until the panel reported it
+pub fn state_json(state: &SpeedEditorState) -> Value {
+    let battery = state.battery.map(|status| {
+        json!({
+            "level_percent": status.level_percent,
+            "charging": status.charging,
+        })
+    });
+    json!({
+        "type": "state",
+        "connected": state.connected,
+        "keys": names(&state.current_keys),
+        "key_leds": names(&state.current_key_leds),
+        "jog_mode": state.current_jog_mode.to_string(),
+        "jog_leds": names(&state.current_jog_leds),
+        "battery": battery,
+    })
+}
+
+fn names<T: std::fmt::Debug>(values: &[T]) -> Vec<String> {
+    values.iter().map(|v| format!("{:?}", v)).collect()
+}
+
+fn field<'a>(message: &'a Value, name: &str) -> Result<&'a Value, String> {
+    message
+        .get(name)
+        .ok_or_else(|| format!("missing field `{}`", name))
+}
+
+fn on(message: &Value) -> Result<bool, String> {
+    field(message, "on")?
+        .as_bool()
+        .ok_or_else(|| "`on` must be a boolean".to_string())
+}
+
+fn name<'a>(message: &'a Value, field_name: &str) -> Result<&'a str, String> {
+    field(message, field_name)?
+        .as_str()
+        .ok_or_else(|| format!("`{}` must be a string", field_name))
+}
+
+fn key_led(name: &str) -> Result<KeyLed, String> {
+    KeyLed::from_str(name).map_err(|_| format!("unknown key led `{}`", name))
+}
+
+// The command for a JSON message:
+// {"type": "key_led", "led": "Cam1", "on": true}, {"type": "key_leds", "leds": ["Cut"], "on": false},
+// {"type": "all_key_leds", "on": false}, {"type": "jog_led", "led": "Shtl", "on": true},
+// {"type": "all_jog_leds", "on": false} and {"type": "jog_mode", "mode": "shuttle"}
+pub(crate) fn command(message: &Value) -> Result<Command, String> {
+    match name(message, "type")? {
+        "key_led" => Ok(Command::KeyLed(
+            key_led(name(message, "led")?)?,
+            on(message)?,
+        )),
+        "key_leds" => {
+            let leds = field(message, "leds")?
+                .as_array()
+                .ok_or_else(|| "`leds` must be an array".to_string())?
+                .iter()
+                .map(|led| {
+                    led.as_str()
+                        .ok_or_else(|| "`leds` must hold strings".to_string())
+                        .and_then(key_led)
+                })
+                .collect::<Result<Vec<KeyLed>, String>>()?;
+            Ok(Command::Leds(leds, on(message)?))
+        }
+        "all_key_leds" => Ok(Command::AllKeyLeds(on(message)?)),
+        "jog_led" => {
+            let led = name(message, "led")?;
+            let led = JogLed::from_str(led).map_err(|_| format!("unknown jog led `{}`", led))?;
+            Ok(Command::JogLed(led, on(message)?))
+        }
+        "all_jog_leds" => Ok(Command::AllJogLeds(on(message)?)),
+        "jog_mode" => {
+            let mode = name(message, "mode")?;
+            let mode =
+                JogMode::from_name(mode).ok_or_else(|| format!("unknown jog mode `{}`", mode))?;
+            Ok(Command::JogMode(mode))
+        }
+        other => Err(format!("unknown message type `{}`", other)),
+    }
+}
+
+fn ws_error(e: tungstenite::Error) -> io::Error {
+    match e {
+        tungstenite::Error::Io(e) => e,
+        e => io::Error::other(e.to_string()),
+    }
+}
+
+fn error_json(message: &str) -> Value {
+    json!({ "type": "error", "message": message })
+}
+
+// Serves panel events to WebSocket clients as JSON and applies the LED and
+// jog mode commands they send. Invalid commands are answered with
+// {"type": "error", "message": "..."}.
+pub struct WsServer {
+    listener: TcpListener,
+}
+
+impl WsServer {
+    const POLL_INTERVAL: u64 = 20;
+    const HANDSHAKE_TIMEOUT: u64 = 5000;
+
+    pub fn bind(addr: SocketAddr) -> Result<WsServer, SpeedEditorError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(WsServer { listener })
+    }
+
+    // Useful when bound to port 0
+    pub fn local_addr(&self) -> Result<SocketAddr, SpeedEditorError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn run(self, speed_editor: SpeedEditor) -> SpeedEditorResult {
+        self.run_until(speed_editor, &StopToken::new())
+    }
+
+    pub fn run_until(self, speed_editor: SpeedEditor, token: &StopToken) -> SpeedEditorResult {
+        let clients: Arc<Mutex<Vec<Sender<String>>>> = Arc::new(Mutex::new(vec![]));
+        let broadcast = clients.clone();
+        let on_event = move |event| {
+            let text = event_json(&event).to_string();
+            // Clients that went away are dropped
+            broadcast
+                .lock()
+                .unwrap()
+                .retain(|client| client.send(text.clone()).is_ok());
+            true
+        };
+
+        let done = StopToken::new();
+        let mut sessions: Vec<JoinHandle<()>> = vec![];
+        let interval = Duration::from_millis(Self::POLL_INTERVAL);
+        let result = speed_editor.run_bridge(token, interval, on_event, |controller| {
+            sessions.retain(|session| !session.is_finished());
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let (tx, rx) = channel();
+                    clients.lock().unwrap().push(tx);
+                    let controller = controller.clone();
+                    let done = done.clone();
+                    sessions.push(thread::spawn(move || {
+                        // A client failing only ends its own session
+                        let _ = Self::serve(stream, controller, rx, done);
+                    }));
+                    Ok(true)
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+                Err(e) => Err(e.into()),
+            }
+        });
+
+        done.stop();
+        for session in sessions {
+            let _ = session.join();
+        }
+        result
+    }
+
+    fn serve(
+        stream: TcpStream,
+        controller: Controller,
+        events: Receiver<String>,
+        done: StopToken,
+    ) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_millis(Self::HANDSHAKE_TIMEOUT)))?;
+        let mut ws = tungstenite::accept(stream).map_err(|e| match e {
+            tungstenite::HandshakeError::Failure(e) => ws_error(e),
+            tungstenite::HandshakeError::Interrupted(_) => io::ErrorKind::TimedOut.into(),
+        })?;
+        ws.get_ref()
+            .set_read_timeout(Some(Duration::from_millis(Self::POLL_INTERVAL)))?;
+        ws.send(Message::text(state_json(&controller.state()).to_string()))
+            .map_err(ws_error)?;
+
+        while !done.is_stopped() {
+            for text in events.try_iter() {
+                ws.send(Message::text(text)).map_err(ws_error)?;
+            }
+            match ws.read() {
+                Ok(Message::Text(text)) => Self::apply(&mut ws, &controller, &text)?,
+                Ok(Message::Close(_)) => break,
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => return Err(ws_error(e)),
+            }
+        }
+
+        let _ = ws.close(None);
+        let _ = ws.flush();
+        Ok(())
+    }
+
+    fn apply(ws: &mut WebSocket<TcpStream>, controller: &Controller, text: &str) -> io::Result<()> {
+        let result = serde_json::from_str::<Value>(text)
+            .map_err(|e| format!("invalid JSON: {}", e))
+            .and_then(|message| command(&message));
+        let error = match result {
+            Ok(command) => match controller.send(command) {
+                Ok(()) => return Ok(()),
+                Err(_) => "the panel is stopped".to_string(),
+            },
+            Err(message) => message,
+        };
+        ws.send(Message::text(error_json(&error).to_string()))
+            .map_err(ws_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command, event_json, state_json, WsServer};
+    use crate::{
+        speed_editor::{
+            command::Command,
+            mock::fixtures::{connecting, wait_until, CAM1_LIT},
+        },
+        BatteryStatus, Event, Gesture, JogMode, Key, KeyLed, MockTransport, SpeedEditorState,
+        StopToken,
+    };
+    use serde_json::{json, Value};
+    use std::thread;
+    use tungstenite::Message;
+
+    #[test]
+    fn state_as_json() {
+        let mut state = SpeedEditorState {
+            connected: true,
+            current_keys: vec![Key::Cut],
+            current_key_leds: vec![KeyLed::Cam1],
+            current_jog_mode: JogMode::Relative,
+            current_jog_leds: vec![],
+            battery: None,
+        };
+        assert_eq!(state_json(&state)["battery"], Value::Null);
+
+        state.battery = Some(BatteryStatus {
+            level_percent: 80,
+            charging: true,
+        });
+        assert_eq!(
+            state_json(&state),
+            json!({
+                "type": "state",
+                "connected": true,
+                "keys": ["Cut"],
+                "key_leds": ["Cam1"],
+                "jog_mode": "Relative",
+                "jog_leds": [],
+                "battery": { "level_percent": 80, "charging": true },
+            })
+        );
+    }
+
+    #[test]
+    fn events_as_json() {
+        assert_eq!(
+            event_json(&Event::KeyDown(Key::Cut)),
+            json!({ "type": "key_down", "key": "Cut" })
+        );
+        assert_eq!(
+            event_json(&Event::Jog(JogMode::Absolute, -120)),
+            json!({ "type": "jog", "mode": "Absolute", "value": -120 })
+        );
+        assert_eq!(
+            event_json(&Event::Gesture(Gesture::Held(Key::In, 2))),
+            json!({ "type": "held", "key": "In", "count": 2 })
+        );
+    }
+
+    #[test]
+    fn commands_from_json() {
+        assert_eq!(
+            command(&json!({ "type": "key_led", "led": "Cam1", "on": true })),
+            Ok(Command::KeyLed(KeyLed::Cam1, true))
+        );
+        assert_eq!(
+            command(&json!({ "type": "key_leds", "leds": ["Cut", "Dis"], "on": false })),
+            Ok(Command::Leds(vec![KeyLed::Cut, KeyLed::Dis], false))
+        );
+        assert_eq!(
+            command(&json!({ "type": "jog_mode", "mode": "shuttle" })),
+            Ok(Command::JogMode(JogMode::Absolute))
+        );
+        assert_eq!(
+            command(&json!({ "type": "key_led", "led": "Camm1", "on": true })),
+            Err("unknown key led `Camm1`".to_string())
+        );
+        assert_eq!(
+            command(&json!({ "type": "all_key_leds" })),
+            Err("missing field `on`".to_string())
+        );
+    }
+
+    // The next message of type `kind`, others are skipped
+    fn next_json(
+        ws: &mut tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>,
+        kind: &str,
+    ) -> Value {
+        loop {
+            if let Message::Text(text) = ws.read().unwrap() {
+                let message: Value = serde_json::from_str(text.as_str()).unwrap();
+                if message["type"] == kind {
+                    return message;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clients_get_events_and_drive_leds() {
+        let transport = MockTransport::new();
+        let se = connecting(&transport);
+
+        let server = WsServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = format!("ws://{}", server.local_addr().unwrap());
+        let token = StopToken::new();
+        let stop = token.clone();
+        let handle = thread::spawn(move || server.run_until(se, &stop));
+
+        let (mut ws, _) = tungstenite::connect(url).unwrap();
+        assert_eq!(next_json(&mut ws, "state")["connected"], true);
+
+        ws.send(Message::text(
+            r#"{"type": "key_led", "led": "Cam1", "on": true}"#,
+        ))
+        .unwrap();
+        ws.send(Message::text(
+            r#"{"type": "key_led", "led": "Nope", "on": true}"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            next_json(&mut ws, "error"),
+            json!({ "type": "error", "message": "unknown key led `Nope`" })
+        );
+
+        transport.push_input_report(&[0x4, 0xf, 0x0]);
+        assert_eq!(next_json(&mut ws, "key_down")["key"], "Cut");
+
+        let lit = wait_until(|| transport.written().contains(&CAM1_LIT.to_vec()));
+
+        token.stop();
+        handle.join().unwrap().unwrap();
+        assert!(lit);
+    }
+}